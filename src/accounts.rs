@@ -0,0 +1,184 @@
+use std::fs;
+
+use crate::special_bits::{GROUP_WRITE, OTHER_WRITE, USER_WRITE};
+
+/// Entry of the password database.
+#[derive(Debug, Clone, PartialEq)]
+pub struct User {
+    pub name: String,
+    pub uid: u32,
+    pub gid: u32,
+}
+
+/// Entry of the group database.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Group {
+    pub name: String,
+    pub gid: u32,
+    pub members: Vec<String>,
+}
+
+/// Users and groups as listed in `/etc/passwd` and `/etc/group`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Accounts {
+    pub users: Vec<User>,
+    pub groups: Vec<Group>,
+}
+
+impl Accounts {
+    /// Reads the local user and group databases.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `/etc/passwd` or `/etc/group` can't be read.
+    pub fn load() -> anyhow::Result<Self> {
+        Ok(Self::parse(
+            &fs::read_to_string("/etc/passwd")?,
+            &fs::read_to_string("/etc/group")?,
+        ))
+    }
+
+    /// Parses the contents of a passwd and a group file, skipping malformed lines.
+    #[must_use]
+    pub fn parse(passwd: &str, group: &str) -> Self {
+        let users = passwd
+            .lines()
+            .filter_map(|line| {
+                let fields: Vec<&str> = line.split(':').collect();
+
+                Some(User {
+                    name: (*fields.first()?).to_string(),
+                    uid: fields.get(2)?.parse().ok()?,
+                    gid: fields.get(3)?.parse().ok()?,
+                })
+            })
+            .collect();
+
+        let groups = group
+            .lines()
+            .filter_map(|line| {
+                let fields: Vec<&str> = line.split(':').collect();
+
+                Some(Group {
+                    name: (*fields.first()?).to_string(),
+                    gid: fields.get(2)?.parse().ok()?,
+                    members: fields
+                        .get(3)?
+                        .split(',')
+                        .filter(|member| !member.is_empty())
+                        .map(ToString::to_string)
+                        .collect(),
+                })
+            })
+            .collect();
+
+        Self { users, groups }
+    }
+
+    /// Returns the uid of the user called `name`.
+    #[must_use]
+    pub fn uid_of(&self, name: &str) -> Option<u32> {
+        self.users
+            .iter()
+            .find(|user| user.name == name)
+            .map(|user| user.uid)
+    }
+
+    /// Returns the gid of the group called `name`.
+    #[must_use]
+    pub fn gid_of(&self, name: &str) -> Option<u32> {
+        self.groups
+            .iter()
+            .find(|group| group.name == name)
+            .map(|group| group.gid)
+    }
+
+    /// Returns the primary gid of the user with `uid`.
+    #[must_use]
+    pub fn primary_gid_of(&self, uid: u32) -> Option<u32> {
+        self.users
+            .iter()
+            .find(|user| user.uid == uid)
+            .map(|user| user.gid)
+    }
+
+    /// Returns the primary and supplementary gids of the user with `uid`.
+    #[must_use]
+    pub fn gids_of(&self, uid: u32) -> Vec<u32> {
+        let Some(user) = self.users.iter().find(|user| user.uid == uid) else {
+            return Vec::new();
+        };
+
+        let mut gids = vec![user.gid];
+        gids.extend(
+            self.groups
+                .iter()
+                .filter(|group| group.members.contains(&user.name))
+                .map(|group| group.gid),
+        );
+
+        gids
+    }
+}
+
+/// Returns whether the user `uid`, member of `gids`, may write to an object with `mode`
+/// owned by `owner_uid` and `owner_gid`.
+///
+/// Like the kernel, only the permission class matching first (owner, group, other) is
+/// considered and root may always write.
+#[must_use]
+#[allow(clippy::similar_names)]
+pub fn can_write(mode: u32, owner_uid: u32, owner_gid: u32, uid: u32, gids: &[u32]) -> bool {
+    if uid == 0 {
+        true
+    } else if uid == owner_uid {
+        mode & USER_WRITE != 0
+    } else if gids.contains(&owner_gid) {
+        mode & GROUP_WRITE != 0
+    } else {
+        mode & OTHER_WRITE != 0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::accounts::{Accounts, can_write};
+
+    #[test]
+    fn test_gids_of() {
+        let accounts = Accounts::parse(
+            "root:x:0:0:root:/root:/bin/bash\n\
+             alice:x:1000:1000:Alice:/home/alice:/bin/bash\n\
+             bob:x:1001:1001::/home/bob:/bin/sh\n\
+             broken line\n",
+            "root:x:0:\n\
+             alice:x:1000:\n\
+             bob:x:1001:\n\
+             www-data:x:33:alice,bob\n\
+             docker:x:999:alice\n",
+        );
+
+        assert_eq!(accounts.users.len(), 3);
+        assert_eq!(accounts.gids_of(1000), vec![1000, 33, 999]);
+        assert_eq!(accounts.gids_of(1001), vec![1001, 33]);
+        assert_eq!(accounts.gids_of(0), vec![0]);
+        assert_eq!(accounts.gids_of(4242), Vec::<u32>::new());
+    }
+
+    #[test]
+    fn test_can_write() {
+        let gids = [1000, 33];
+
+        // Owner
+        assert!(can_write(0o644, 1000, 0, 1000, &gids));
+        assert!(!can_write(0o466, 1000, 33, 1000, &gids));
+        // Group
+        assert!(can_write(0o664, 0, 33, 1000, &gids));
+        assert!(!can_write(0o646, 0, 33, 1000, &gids));
+        // Other
+        assert!(can_write(0o646, 0, 0, 1000, &gids));
+        assert!(!can_write(0o664, 0, 0, 1000, &gids));
+        // Root
+        assert!(can_write(0o000, 1000, 1000, 0, &[0]));
+    }
+}
@@ -1,8 +1,15 @@
-use std::path::PathBuf;
+use std::{ffi::OsString, path::PathBuf, time::Duration};
 
-use clap::Parser;
+use clap::{Parser, builder::PossibleValuesParser};
+use globset::Glob;
 
-use crate::input_parser::FilterSet;
+use crate::{
+    config::ScanConfig,
+    input_parser::FilterSet,
+    output::{ColorChoice, ModeDisplay, ObjectKind, OutputFormat, QuotePaths, SortOrder},
+    presets::BUILTIN_PROFILE_NAMES,
+    util::{parse_duration, parse_size, parse_timestamp, unix_now},
+};
 
 #[derive(Parser, Debug)]
 #[command(
@@ -10,6 +17,7 @@ use crate::input_parser::FilterSet;
     about,
     long_about = "Simple search for finding mistakes in filesystem owner and permission settings"
 )]
+#[allow(clippy::struct_excessive_bools)]
 pub struct Args {
     /// List of allowed directory types
     #[arg(short, long)]
@@ -19,6 +27,142 @@ pub struct Args {
     #[arg(short, long)]
     pub file_filter: Option<FilterSet>,
 
+    /// List of reported types, objects matching one are reported even if the other filters
+    /// allow them
+    #[arg(long)]
+    pub report_filter: Option<FilterSet>,
+
+    /// Only report regular files owned by uid 0 with the setuid bit set
+    #[arg(long, conflicts_with_all = ["directory_filter", "file_filter", "report_filter", "filter_preset", "profile_name"])]
+    pub suid_root: bool,
+
+    /// Only report regular files owned by gid 0 with the setgid bit set
+    #[arg(long, conflicts_with_all = ["directory_filter", "file_filter", "report_filter", "filter_preset", "profile_name"])]
+    pub sgid_root: bool,
+
+    /// Only report objects the user with UID may write to
+    #[arg(
+        long,
+        value_name = "UID",
+        conflicts_with_all = [
+            "directory_filter",
+            "file_filter",
+            "report_filter",
+            "filter_preset",
+            "profile_name",
+            "suid_root",
+            "sgid_root",
+        ]
+    )]
+    pub effective_write_for: Option<u32>,
+
+    /// Named filter preset used for any filter that isn't given explicitly
+    #[arg(long, value_name = "NAME")]
+    pub filter_preset: Option<String>,
+
+    /// Built-in filter profile used for any filter that isn't given explicitly
+    #[arg(
+        long,
+        value_name = "NAME",
+        value_parser = PossibleValuesParser::new(BUILTIN_PROFILE_NAMES),
+        conflicts_with = "filter_preset"
+    )]
+    pub profile_name: Option<String>,
+
+    /// File to load filter presets from [default: ~/.config/permsearch/presets]
+    #[arg(long, value_name = "FILE", requires = "filter_preset")]
+    pub presets_file: Option<PathBuf>,
+
+    /// Starts a comment in the presets file, either on its own line or after an entry
+    #[arg(
+        long,
+        value_name = "PREFIX",
+        default_value = "#",
+        value_parser = clap::builder::NonEmptyStringValueParser::new()
+    )]
+    pub filter_comment_prefix: String,
+
+    /// Policy file whose findings are compared to the active filters, with one
+    /// `directory = <filter>` and/or `file = <filter>` line
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["suid_root", "sgid_root", "effective_write_for"]
+    )]
+    pub compare_policy: Option<PathBuf>,
+
+    /// Treat unset ("-") read, write and execute bits in filters as wildcards
+    #[arg(long)]
+    pub wildcard_unspecified: bool,
+
+    /// Constrains the group of filters naming their user with u:<NAME> but no group to the
+    /// primary group of that user, an explicit g token takes precedence
+    #[arg(long)]
+    pub imply_primary_group: bool,
+
+    /// Lexically collapses "." and ".." in the base directory without resolving symlinks
+    #[arg(long)]
+    pub normalize_base: bool,
+
+    /// Only prints the number of findings at the end
+    #[arg(long, conflicts_with_all = ["csv", "format", "print0", "template_file"])]
+    pub count: bool,
+
+    /// Print findings as CSV with a header row instead of the default output
+    #[arg(long, conflicts_with = "format")]
+    pub csv: bool,
+
+    /// Format of the findings
+    #[arg(long, value_name = "FORMAT", value_enum, default_value_t)]
+    pub format: OutputFormat,
+
+    /// Print only the paths of findings, each terminated by a NUL byte (for xargs -0)
+    #[arg(long, conflicts_with_all = ["format", "csv"])]
+    pub print0: bool,
+
+    /// Writes every traversal decision (entered, excluded, flagged, ...) as a JSON object per
+    /// line to FILE
+    #[arg(long, value_name = "FILE")]
+    pub trace_json: Option<PathBuf>,
+
+    /// Prints findings with the template in FILE, see the README for the placeholders
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["format", "csv", "print0"])]
+    pub template_file: Option<PathBuf>,
+
+    /// Additionally report objects granting "other" a permission that "group" doesn't have
+    #[arg(long)]
+    pub other_not_exceeding_group: bool,
+
+    /// Prints Rust code building the directory and file filters with this crate and exits
+    #[arg(long, conflicts_with = "check_path")]
+    pub emit_rust: bool,
+
+    /// Prints a `find` command listing the objects that match none of the filters and exits
+    #[arg(long, conflicts_with_all = ["check_path", "emit_rust"])]
+    pub emit_find: bool,
+
+    /// Only checks PATH against the filters and prints whether it passes and why not
+    #[arg(long, value_name = "PATH")]
+    pub check_path: Option<PathBuf>,
+
+    /// Only reports directories of the search path, or their entries, that are world-writable
+    /// or not owned by root
+    #[arg(long, conflicts_with_all = ["check_path", "emit_rust"])]
+    pub check_path_dirs: bool,
+
+    /// Only reports directories directly below the base directory (like /home) that aren't
+    /// owned by the user of the same name or are accessible by group or others
+    #[arg(long, conflicts_with_all = ["check_path", "check_path_dirs", "emit_rust"])]
+    pub home_dirs: bool,
+
+    /// Colon-separated directories audited by --check-path-dirs instead of $PATH
+    #[arg(long, value_name = "DIRS", requires = "check_path_dirs")]
+    pub path_list: Option<OsString>,
+
+    /// Never report world-writable objects with the sticky bit set (like /tmp)
+    #[arg(long)]
+    pub exclude_sticky_world_writable: bool,
+
     /// Remove active config from output
     #[arg(short, long)]
     pub silent: bool,
@@ -27,7 +171,292 @@ pub struct Args {
     #[arg(short, long)]
     pub ignore_symlinks: bool,
 
-    /// Base directory to work upon
-    #[arg()]
+    /// Skips objects whose path relative to the base directory matches GLOB (e.g.
+    /// `**/node_modules`), can be given multiple times
+    #[arg(long, value_name = "GLOB", value_parser = Glob::new)]
+    pub exclude: Vec<Glob>,
+
+    /// Silently skips paths too long for the system (ENAMETOOLONG) instead of reporting
+    /// access errors
+    #[arg(long)]
+    pub skip_long_paths: bool,
+
+    /// Skips objects on other filesystems than the base directory, like `find -xdev`
+    #[arg(short = 'x', long)]
+    pub one_file_system: bool,
+
+    /// Still searches the filesystem PATH is on with --one-file-system, can be given multiple
+    /// times
+    #[arg(long, value_name = "PATH", requires = "one_file_system")]
+    pub cross_into: Vec<PathBuf>,
+
+    /// Searches the directories symlinks point to, except those already searched. A directory
+    /// reached through a symlink first is searched again under its own path
+    #[arg(long, conflicts_with = "ignore_symlinks")]
+    pub follow_symlinks: bool,
+
+    /// Reports the objects below a followed symlink under the resolved path of its target,
+    /// noting the symlink
+    #[arg(long, requires = "follow_symlinks")]
+    pub report_under_target: bool,
+
+    /// Skips the device directory when scanning from a mount root and checks the device nodes
+    /// there by their directory entries, without resolving their paths
+    #[arg(long)]
+    pub safe_root: bool,
+
+    /// Prints how many files, directories and symlinks were examined
+    #[arg(long)]
+    pub scanned_by_type: bool,
+
+    /// Prints how many objects of every file type, including devices, fifos and sockets, were examined
+    #[arg(long)]
+    pub types_summary: bool,
+
+    /// Prints the number of examined objects per octal mode to stderr at the end
+    #[arg(long)]
+    pub permission_histogram: bool,
+
+    /// Prints the N directories containing the most findings at the end
+    #[arg(long, value_name = "N")]
+    pub worst_dirs: Option<usize>,
+
+    /// Prints how many findings were modified within 1 day, 7 days, 30 days, 1 year or earlier
+    #[arg(long)]
+    pub age_buckets: bool,
+
+    /// Flags files in setgid directories whose group differs from the directory's group
+    #[arg(long)]
+    pub setgid_inheritance_check: bool,
+
+    /// Prints directories in which the same mode is shared by objects of different owners
+    #[arg(long)]
+    pub owner_inconsistency: bool,
+
+    /// Only reports objects created less than DURATION (e.g. 12h, 7d) ago, if the filesystem
+    /// records creation times
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub created_newer_than: Option<Duration>,
+
+    /// Only checks objects of these types, directories are still searched
+    #[arg(long = "type", value_name = "TYPE", value_enum, value_delimiter = ',')]
+    pub object_types: Vec<ObjectKind>,
+
+    /// Never report empty regular files
+    #[arg(long)]
+    pub skip_empty_files: bool,
+
+    /// Only reports objects modified after TIME, either a duration ago (e.g. 6h, 30d),
+    /// @SECONDS since the epoch or a UTC date like 2024-05-01[T12:00[:00]]
+    #[arg(long, value_name = "TIME", value_parser = |value: &str| parse_timestamp(value, unix_now()))]
+    pub newer_than: Option<i64>,
+
+    /// Only reports objects modified before TIME, in the same format as --newer-than
+    #[arg(long, value_name = "TIME", value_parser = |value: &str| parse_timestamp(value, unix_now()))]
+    pub older_than: Option<i64>,
+
+    /// Only reports objects of at least SIZE bytes (e.g. 500k, 10M), regardless of the filters
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    pub min_size: Option<u64>,
+
+    /// Only reports objects of at most SIZE bytes (e.g. 500k, 10M), regardless of the filters
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    pub max_size: Option<u64>,
+
+    /// Only reports objects owned by a uid of at least UID, regardless of the filters
+    #[arg(long, value_name = "UID")]
+    pub min_uid: Option<u32>,
+
+    /// Only reports objects owned by a uid of at most UID, regardless of the filters
+    #[arg(long, value_name = "UID")]
+    pub max_uid: Option<u32>,
+
+    /// Prints the number of flagged files per extension at the end
+    #[arg(long)]
+    pub by_extension: bool,
+
+    /// Only examine about 1 in N objects, chosen deterministically by path
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub sample: Option<u32>,
+
+    /// Stops the search once N findings were reported
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    pub limit: Option<u64>,
+
+    /// Aborts the search once more than N access errors occurred
+    #[arg(long, value_name = "N")]
+    pub error_budget: Option<u64>,
+
+    /// Warns and exits successfully instead of failing if the base directory doesn't exist
+    #[arg(long)]
+    pub allow_missing_base: bool,
+
+    /// Treats file names that aren't valid UTF-8 as access errors instead of printing them lossily
+    #[arg(long)]
+    pub strict_utf8_paths: bool,
+
+    /// Reports a panic while checking an object as an access error and continues the search
+    #[arg(long)]
+    pub continue_on_panic: bool,
+
+    /// Prints access errors as one block after the findings and summaries instead of inline
+    #[arg(long)]
+    pub defer_errors: bool,
+
+    /// Aborts the search at the first access error
+    #[arg(long, conflicts_with = "error_budget")]
+    pub fail_fast_on_error: bool,
+
+    /// Appends whether a flagged symlink points to a file, a directory or nothing
+    #[arg(long)]
+    pub annotate_symlink_target_type: bool,
+
+    /// Prints every hop of flagged symlinks until the final target, a loop or a broken link
+    #[arg(long)]
+    pub resolve_chain: bool,
+
+    /// How the permissions of findings are shown
+    #[arg(long, value_name = "FORMAT", value_enum, default_value_t)]
+    pub mode_display: ModeDisplay,
+
+    /// Prints the owners and mode of every directory from the base directory down to each finding
+    #[arg(long)]
+    pub show_parent_chain: bool,
+
+    /// When the permissions of findings are colored
+    #[arg(long, value_name = "WHEN", value_enum, default_value_t)]
+    pub color: ColorChoice,
+
+    /// How paths of findings are quoted
+    #[arg(long, value_name = "STYLE", value_enum, default_value_t)]
+    pub quote_paths: QuotePaths,
+
+    /// Adds a boolean field per permission bit, including the special bits, to the findings
+    /// of --format json
+    #[arg(long)]
+    pub json_flatten_perms: bool,
+
+    /// Appends the raw file mode, including the file type bits, to each finding
+    #[arg(long)]
+    pub dump_mode_bits: bool,
+
+    /// Doesn't descend into directories more than N levels below the base directory, which is
+    /// level 0
+    #[arg(long, value_name = "N")]
+    pub max_depth: Option<usize>,
+
+    /// Doesn't check objects less than N levels below the base directory, but still descends
+    /// into them
+    #[arg(long, value_name = "N")]
+    pub min_depth: Option<usize>,
+
+    /// Visits directory entries in a pseudo-random order derived from SEED
+    #[arg(long, value_name = "SEED")]
+    pub shuffle_seed: Option<u64>,
+
+    /// Visits directory entries in the order of the filesystem instead of sorted by name,
+    /// which is faster for huge directories
+    #[arg(long, conflicts_with = "shuffle_seed")]
+    pub no_sort: bool,
+
+    /// Order in which directory entries are visited
+    #[arg(
+        long,
+        value_name = "KEY",
+        value_enum,
+        default_value_t,
+        conflicts_with_all = ["shuffle_seed", "no_sort"]
+    )]
+    pub sort_by: SortOrder,
+
+    /// Sorts directory entries by name ignoring ASCII case, names differing only in case
+    /// still in a fixed order
+    #[arg(long, conflicts_with_all = ["shuffle_seed", "no_sort"])]
+    pub sort_case_insensitive: bool,
+
+    /// Prints the time spent reading directories, reading metadata and printing at the end
+    #[arg(long)]
+    pub profile: bool,
+
+    /// Annotates flagged files inside a git repository with their last author
+    #[arg(long)]
+    pub git_blame: bool,
+
+    /// Base directory to work upon, optional with --check-path (where it defaults to /) and
+    /// --check-path-dirs
+    #[arg(
+        required_unless_present_any = ["check_path", "check_path_dirs"],
+        default_value = "/",
+        hide_default_value = true
+    )]
     pub base_dir: PathBuf,
 }
+
+/// Copies the search options, without applying presets, profiles or filter modifiers yet.
+impl From<&Args> for ScanConfig {
+    fn from(args: &Args) -> Self {
+        Self {
+            base_dir: args.base_dir.clone(),
+            directory_filter: args.directory_filter.clone(),
+            file_filter: args.file_filter.clone(),
+            report_filter: args.report_filter.clone(),
+            suid_root: args.suid_root,
+            sgid_root: args.sgid_root,
+            effective_write_for: args.effective_write_for,
+            count: args.count,
+            csv: args.csv,
+            format: args.format,
+            print0: args.print0,
+            trace_json: args.trace_json.clone(),
+            other_not_exceeding_group: args.other_not_exceeding_group,
+            exclude_sticky_world_writable: args.exclude_sticky_world_writable,
+            silent: args.silent,
+            ignore_symlinks: args.ignore_symlinks,
+            skip_long_paths: args.skip_long_paths,
+            one_file_system: args.one_file_system,
+            follow_symlinks: args.follow_symlinks,
+            report_under_target: args.report_under_target,
+            safe_root: args.safe_root,
+            scanned_by_type: args.scanned_by_type,
+            types_summary: args.types_summary,
+            permission_histogram: args.permission_histogram,
+            worst_dirs: args.worst_dirs,
+            age_buckets: args.age_buckets,
+            setgid_inheritance_check: args.setgid_inheritance_check,
+            owner_inconsistency: args.owner_inconsistency,
+            created_newer_than: args.created_newer_than,
+            object_types: args.object_types.clone(),
+            skip_empty_files: args.skip_empty_files,
+            newer_than: args.newer_than,
+            older_than: args.older_than,
+            min_size: args.min_size,
+            max_size: args.max_size,
+            min_uid: args.min_uid,
+            max_uid: args.max_uid,
+            by_extension: args.by_extension,
+            sample: args.sample,
+            limit: args.limit,
+            error_budget: args.error_budget,
+            strict_utf8_paths: args.strict_utf8_paths,
+            continue_on_panic: args.continue_on_panic,
+            defer_errors: args.defer_errors,
+            fail_fast_on_error: args.fail_fast_on_error,
+            annotate_symlink_target_type: args.annotate_symlink_target_type,
+            resolve_chain: args.resolve_chain,
+            mode_display: args.mode_display,
+            show_parent_chain: args.show_parent_chain,
+            quote_paths: args.quote_paths,
+            json_flatten_perms: args.json_flatten_perms,
+            dump_mode_bits: args.dump_mode_bits,
+            max_depth: args.max_depth,
+            min_depth: args.min_depth,
+            shuffle_seed: args.shuffle_seed,
+            no_sort: args.no_sort,
+            sort_by: args.sort_by,
+            sort_case_insensitive: args.sort_case_insensitive,
+            profile: args.profile,
+            git_blame: args.git_blame,
+            ..Self::default()
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::PathBuf,
+    sync::{Arc, Mutex, mpsc::SyncSender},
+    time::Duration,
+};
+
+use globset::GlobSet;
+
+use crate::{
+    Violation,
+    input_parser::FilterSet,
+    output::{ModeDisplay, ObjectKind, OutputFormat, QuotePaths, SortOrder, Template},
+};
+
+/// Configuration of a search, as taken by [`scan`](crate::scan) and
+/// [`violations`](crate::violations).
+///
+/// Fields without a comment work like the command line option of the same name, see
+/// [`Args`](crate::cli::Args). Unlike there, everything is already resolved: presets, profiles,
+/// `--wildcard-unspecified` and `--imply-primary-group` are applied to the filters.
+#[derive(Debug, Clone, Default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct ScanConfig {
+    pub base_dir: PathBuf,
+    pub directory_filter: Option<FilterSet>,
+    pub file_filter: Option<FilterSet>,
+    pub report_filter: Option<FilterSet>,
+    pub suid_root: bool,
+    pub sgid_root: bool,
+    pub effective_write_for: Option<u32>,
+    /// Groups of the `effective_write_for` user
+    pub effective_write_gids: Vec<u32>,
+    /// This configuration with the filters of the compared policy
+    pub compare_config: Option<Box<ScanConfig>>,
+    pub count: bool,
+    pub csv: bool,
+    pub format: OutputFormat,
+    pub print0: bool,
+    pub trace_json: Option<PathBuf>,
+    /// Template findings are printed with
+    pub template: Option<Template>,
+    pub other_not_exceeding_group: bool,
+    pub exclude_sticky_world_writable: bool,
+    pub silent: bool,
+    pub ignore_symlinks: bool,
+    /// Patterns of the paths relative to the base directory that are skipped
+    pub exclude_set: GlobSet,
+    pub skip_long_paths: bool,
+    pub one_file_system: bool,
+    /// Devices of the filesystems searched despite `one_file_system`, see `--cross-into`
+    pub cross_devices: Vec<u64>,
+    pub follow_symlinks: bool,
+    pub report_under_target: bool,
+    pub safe_root: bool,
+    pub scanned_by_type: bool,
+    pub types_summary: bool,
+    pub permission_histogram: bool,
+    pub worst_dirs: Option<usize>,
+    pub age_buckets: bool,
+    pub setgid_inheritance_check: bool,
+    pub owner_inconsistency: bool,
+    pub created_newer_than: Option<Duration>,
+    pub object_types: Vec<ObjectKind>,
+    pub skip_empty_files: bool,
+    pub newer_than: Option<i64>,
+    pub older_than: Option<i64>,
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    pub min_uid: Option<u32>,
+    pub max_uid: Option<u32>,
+    pub by_extension: bool,
+    pub sample: Option<u32>,
+    pub limit: Option<u64>,
+    pub error_budget: Option<u64>,
+    pub strict_utf8_paths: bool,
+    pub continue_on_panic: bool,
+    pub defer_errors: bool,
+    /// Collects the findings into `ScanStats::violations` instead of printing them, set by
+    /// `scan`
+    pub(crate) collect_violations: bool,
+    /// Passes on the findings and access errors instead of printing them, set by
+    /// `violations`
+    pub(crate) violation_sender: Option<SyncSender<anyhow::Result<Violation>>>,
+    /// Writes the traversal decisions to the `trace_json` file as they happen, set by `run`
+    pub(crate) trace_writer: Option<Arc<Mutex<BufWriter<File>>>>,
+    pub fail_fast_on_error: bool,
+    pub annotate_symlink_target_type: bool,
+    pub resolve_chain: bool,
+    pub mode_display: ModeDisplay,
+    pub show_parent_chain: bool,
+    /// Whether the permissions of findings are colored
+    pub colorize: bool,
+    pub quote_paths: QuotePaths,
+    pub json_flatten_perms: bool,
+    pub dump_mode_bits: bool,
+    pub max_depth: Option<usize>,
+    pub min_depth: Option<usize>,
+    pub shuffle_seed: Option<u64>,
+    pub no_sort: bool,
+    pub sort_by: SortOrder,
+    pub sort_case_insensitive: bool,
+    pub profile: bool,
+    pub git_blame: bool,
+}
+
+impl ScanConfig {
+    /// Searches `base_dir` with the default settings.
+    #[must_use]
+    pub fn new(base_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            base_dir: base_dir.into(),
+            ..Self::default()
+        }
+    }
+}
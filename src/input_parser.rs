@@ -1,19 +1,137 @@
-use std::{fmt::Display, fs::Metadata, os::unix::fs::MetadataExt, str::FromStr};
+use std::{
+    fmt::Display,
+    fs::Metadata,
+    ops::{BitAnd, BitOr},
+    os::unix::fs::MetadataExt,
+    str::FromStr,
+};
 
 use anyhow::{anyhow, bail};
+use serde::Serialize;
 
-#[derive(Debug, PartialEq, Clone)]
+use crate::accounts::Accounts;
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
 pub struct PermissionBlock {
     pub user: PartialPermissionBlock,
     pub group: PartialPermissionBlock,
     pub other: PartialPermissionBlock,
+    /// Set-user-ID, set-group-ID and sticky bit, laid out like the read, write and execute
+    /// bit of an octal digit.
+    pub special: PartialPermissionBlock,
 }
 
 impl PermissionBlock {
+    /// Builds a block from the `st_mode` of an object.
+    ///
+    /// The mode is masked to its low 12 bits (permission and special bits) first, so the file
+    /// type bits never take part in a comparison and objects of different types with the
+    /// same permissions compare as equal.
+    #[must_use]
+    pub fn from_mode_bits(mode: u32) -> Self {
+        let mode = mode & 0o7777;
+
+        let other = PartialPermissionBlock::from_st_mode_digit(mode % 8);
+        let mode = mode / 8;
+        let group = PartialPermissionBlock::from_st_mode_digit(mode % 8);
+        let mode = mode / 8;
+        let user = PartialPermissionBlock::from_st_mode_digit(mode % 8);
+        let mode = mode / 8;
+        let special = PartialPermissionBlock::from_st_mode_digit(mode % 8);
+
+        Self {
+            user,
+            group,
+            other,
+            special,
+        }
+    }
+
+    /// Builds a block from an octal mode like `0o4755`, where every bit is constrained.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `mode` has bits above the special bits set.
+    pub fn from_octal(mode: u32) -> anyhow::Result<Self> {
+        if mode > 0o7777 {
+            bail!("Octal permissions {mode:o} exceed 7777");
+        }
+
+        Ok(Self::from_mode_bits(mode))
+    }
+
+    /// Parses the symbolic permissions at the start of `part`, the special bits are left open.
+    fn parse_symbolic(part: &str) -> anyhow::Result<Self> {
+        let user = PartialPermissionBlock::safe_from_chars(
+            part.get(..3)
+                .ok_or(anyhow!("Failed to extract user permissions"))?,
+        )?;
+        let group = PartialPermissionBlock::safe_from_chars(
+            part.get(3..6)
+                .ok_or(anyhow!("Failed to extract group permissions"))?,
+        )?;
+        let other = PartialPermissionBlock::safe_from_chars(
+            part.get(6..9)
+                .ok_or(anyhow!("Failed to extract other permissions"))?,
+        )?;
+
+        Ok(Self {
+            user,
+            group,
+            other,
+            special: PartialPermissionBlock::WILDCARD,
+        })
+    }
+
+    /// Parses 1 to 4 octal digits, the special bits are only constrained by a fourth digit.
+    fn parse_octal(digits: &str) -> anyhow::Result<Self> {
+        if digits.len() > 4 {
+            bail!("Octal permissions \"{digits}\" have more than 4 digits");
+        }
+
+        let mode = u32::from_str_radix(digits, 8)
+            .map_err(|_| anyhow!("Invalid octal permissions \"{digits}\""))?;
+        let mut block = Self::from_octal(mode)?;
+
+        if digits.len() < 4 {
+            block.special = PartialPermissionBlock::WILDCARD;
+        }
+
+        Ok(block)
+    }
+
+    /// Returns the octal mode of the block, or `None` if it contains wildcards.
+    #[must_use]
+    pub fn to_octal(&self) -> Option<u32> {
+        [&self.special, &self.user, &self.group, &self.other]
+            .iter()
+            .try_fold(0, |mode, block| Some(mode * 8 + block.to_digit()?))
+    }
+
+    #[must_use]
     pub fn is_compatible(&self, other: &Self) -> bool {
         self.user.is_compatible(&other.user)
             && self.group.is_compatible(&other.group)
             && self.other.is_compatible(&other.other)
+            && self.special.is_compatible(&other.special)
+    }
+
+    /// Returns whether "other" is granted a permission that "group" isn't.
+    #[must_use]
+    pub fn other_exceeds_group(&self) -> bool {
+        self.other.grants_more_than(&self.group)
+    }
+
+    /// Turns every unset read, write and execute bit into a wildcard, so only set bits are
+    /// enforced. The special bits are kept as they are.
+    #[must_use]
+    pub fn unset_as_wildcard(&self) -> Self {
+        Self {
+            user: self.user.unset_as_wildcard(),
+            group: self.group.unset_as_wildcard(),
+            other: self.other.unset_as_wildcard(),
+            special: self.special.clone(),
+        }
     }
 }
 
@@ -25,15 +143,35 @@ impl From<Metadata> for PermissionBlock {
 
 impl From<&Metadata> for PermissionBlock {
     fn from(value: &Metadata) -> Self {
-        let mode = value.mode();
+        Self::from_mode_bits(value.mode())
+    }
+}
 
-        let other = PartialPermissionBlock::from_st_mode_digit(mode % 8);
-        let mode = mode / 8;
-        let group = PartialPermissionBlock::from_st_mode_digit(mode % 8);
-        let mode = mode / 8;
-        let user = PartialPermissionBlock::from_st_mode_digit(mode % 8);
+/// Combines two blocks triad by triad, see [`PermissionState`]'s `BitAnd`.
+impl BitAnd for PermissionBlock {
+    type Output = Self;
 
-        Self { user, group, other }
+    fn bitand(self, rhs: Self) -> Self {
+        Self {
+            user: self.user & rhs.user,
+            group: self.group & rhs.group,
+            other: self.other & rhs.other,
+            special: self.special & rhs.special,
+        }
+    }
+}
+
+/// Combines two blocks triad by triad, see [`PermissionState`]'s `BitOr`.
+impl BitOr for PermissionBlock {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self {
+            user: self.user | rhs.user,
+            group: self.group | rhs.group,
+            other: self.other | rhs.other,
+            special: self.special | rhs.special,
+        }
     }
 }
 
@@ -47,14 +185,44 @@ impl Display for PermissionBlock {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize)]
 pub enum PermissionState {
     SET,
     UNSET,
     WILDCARD,
 }
 
-#[derive(Debug, PartialEq, Clone)]
+/// Intersection of two states: a bit is only set if it's set in both.
+///
+/// A wildcard doesn't constrain the result, so it yields the other state.
+impl BitAnd for PermissionState {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        match (self, rhs) {
+            (PermissionState::WILDCARD, state) | (state, PermissionState::WILDCARD) => state,
+            (PermissionState::SET, PermissionState::SET) => PermissionState::SET,
+            _ => PermissionState::UNSET,
+        }
+    }
+}
+
+/// Union of two states: a bit is set if it's set in either.
+///
+/// A wildcard doesn't constrain the result, so it yields the other state.
+impl BitOr for PermissionState {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        match (self, rhs) {
+            (PermissionState::WILDCARD, state) | (state, PermissionState::WILDCARD) => state,
+            (PermissionState::UNSET, PermissionState::UNSET) => PermissionState::UNSET,
+            _ => PermissionState::SET,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
 pub struct PartialPermissionBlock {
     pub read: PermissionState,
     pub write: PermissionState,
@@ -75,7 +243,54 @@ impl Display for PartialPermissionBlock {
     }
 }
 
+impl BitAnd for PartialPermissionBlock {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        Self {
+            read: self.read & rhs.read,
+            write: self.write & rhs.write,
+            execute: self.execute & rhs.execute,
+        }
+    }
+}
+
+impl BitOr for PartialPermissionBlock {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self {
+            read: self.read | rhs.read,
+            write: self.write | rhs.write,
+            execute: self.execute | rhs.execute,
+        }
+    }
+}
+
 impl PartialPermissionBlock {
+    /// States of the read, write and execute bit.
+    fn states(&self) -> [PermissionState; 3] {
+        [self.read, self.write, self.execute]
+    }
+
+    /// Block that doesn't constrain any bit.
+    pub const WILDCARD: Self = Self {
+        read: PermissionState::WILDCARD,
+        write: PermissionState::WILDCARD,
+        execute: PermissionState::WILDCARD,
+    };
+
+    /// Returns the octal digit of the block, or `None` if it contains wildcards.
+    fn to_digit(&self) -> Option<u32> {
+        [(self.read, 4), (self.write, 2), (self.execute, 1)]
+            .iter()
+            .try_fold(0, |digit, (state, value)| match state {
+                PermissionState::SET => Some(digit + value),
+                PermissionState::UNSET => Some(digit),
+                PermissionState::WILDCARD => None,
+            })
+    }
+
     fn from_st_mode_digit(digit: u32) -> Self {
         assert!(
             digit <= 7,
@@ -153,6 +368,29 @@ impl PartialPermissionBlock {
         Ok(permission_block)
     }
 
+    fn unset_as_wildcard(&self) -> Self {
+        let relax = |state| match state {
+            PermissionState::UNSET => PermissionState::WILDCARD,
+            state => state,
+        };
+
+        Self {
+            read: relax(self.read),
+            write: relax(self.write),
+            execute: relax(self.execute),
+        }
+    }
+
+    fn grants_more_than(&self, other: &Self) -> bool {
+        [
+            (self.read, other.read),
+            (self.write, other.write),
+            (self.execute, other.execute),
+        ]
+        .iter()
+        .any(|block| block.0 == PermissionState::SET && block.1 != PermissionState::SET)
+    }
+
     fn is_compatible(&self, other: &Self) -> bool {
         for block in [
             (self.read, other.read),
@@ -172,27 +410,109 @@ impl PartialPermissionBlock {
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+/// Owner id required by a filter: a single id, an inclusive range like `1000-2000` or any of
+/// several ids like `1000|1001|1002`.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
+pub enum IdMatch {
+    Exact(u32),
+    Range(u32, u32),
+    AnyOf(Vec<u32>),
+}
+
+impl IdMatch {
+    #[must_use]
+    pub fn contains(&self, id: u32) -> bool {
+        match self {
+            IdMatch::Exact(expected) => id == *expected,
+            IdMatch::Range(low, high) => (*low..=*high).contains(&id),
+            IdMatch::AnyOf(ids) => ids.contains(&id),
+        }
+    }
+
+    /// Parses the `1000`, `1000-2000` or `1000|1001` after the `u` or `g` of a filter.
+    fn parse(text: &str, filter: &str) -> anyhow::Result<Self> {
+        if text.contains('|') {
+            return Ok(IdMatch::AnyOf(
+                text.split('|').map(str::parse).collect::<Result<_, _>>()?,
+            ));
+        }
+
+        let Some((low, high)) = text.split_once('-') else {
+            return Ok(IdMatch::Exact(text.parse()?));
+        };
+
+        let (low, high) = (low.parse::<u32>()?, high.parse::<u32>()?);
+        if low > high {
+            bail!("Range {low}-{high} in filter \"{filter}\" ends before it starts");
+        }
+
+        Ok(IdMatch::Range(low, high))
+    }
+
+    /// Returns the range covering `self` and `other` if at least one of them is a range and
+    /// they overlap or are adjacent.
+    fn merged(&self, other: &IdMatch) -> Option<IdMatch> {
+        let bounds = |id: &IdMatch| match id {
+            IdMatch::Exact(id) => Some((*id, *id)),
+            IdMatch::Range(low, high) => Some((*low, *high)),
+            IdMatch::AnyOf(_) => None,
+        };
+        let ((low, high), (other_low, other_high)) = (bounds(self)?, bounds(other)?);
+
+        if matches!((self, other), (IdMatch::Exact(_), IdMatch::Exact(_)))
+            || low > other_high.saturating_add(1)
+            || other_low > high.saturating_add(1)
+        {
+            return None;
+        }
+
+        Some(IdMatch::Range(low.min(other_low), high.max(other_high)))
+    }
+}
+
+impl Display for IdMatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IdMatch::Exact(id) => write!(f, "{id}"),
+            IdMatch::Range(low, high) => write!(f, "{low}-{high}"),
+            IdMatch::AnyOf(ids) => {
+                let ids: Vec<String> = ids.iter().map(ToString::to_string).collect();
+                write!(f, "{}", ids.join("|"))
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
 pub struct Filter {
-    pub user_owner: Option<u32>,
-    pub group_owner: Option<u32>,
+    pub user_owner: Option<IdMatch>,
+    pub group_owner: Option<IdMatch>,
     pub permissions: Option<PermissionBlock>,
+    /// Primary gid of the user named with `u:<NAME>`, used by
+    /// [`FilterSet::imply_primary_group`]
+    #[serde(skip)]
+    pub primary_group: Option<u32>,
 }
 
 impl Display for Filter {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut parts: Vec<String> = Vec::new();
 
-        if let Some(uid) = self.user_owner {
-            parts.push(format!("u{}", uid.to_string()));
+        if let Some(uid) = &self.user_owner {
+            parts.push(format!("u{uid}"));
         }
 
-        if let Some(gid) = self.group_owner {
-            parts.push(format!("g{}", gid.to_string()));
+        if let Some(gid) = &self.group_owner {
+            parts.push(format!("g{gid}"));
         }
 
         if let Some(permissions) = &self.permissions {
-            parts.push(permissions.to_string());
+            match permissions.to_octal() {
+                Some(mode) if permissions.special != PartialPermissionBlock::WILDCARD => {
+                    parts.push(format!("{mode:04o}"));
+                }
+                _ => parts.push(permissions.to_string()),
+            }
         }
 
         write!(f, "{}", parts.join(" "))?;
@@ -201,7 +521,212 @@ impl Display for Filter {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+impl Filter {
+    /// Returns the filter allowing what `self` and `other` allow if they only differ in
+    /// overlapping or adjacent user or group ranges.
+    fn merged(&self, other: &Filter) -> Option<Filter> {
+        if self.permissions != other.permissions || self.primary_group != other.primary_group {
+            return None;
+        }
+
+        let merge = |id: &Option<IdMatch>, other_id: &Option<IdMatch>| {
+            id.as_ref()?.merged(other_id.as_ref()?)
+        };
+
+        if self.group_owner == other.group_owner
+            && let Some(user_owner) = merge(&self.user_owner, &other.user_owner)
+        {
+            return Some(Filter {
+                user_owner: Some(user_owner),
+                ..self.clone()
+            });
+        }
+
+        if self.user_owner == other.user_owner
+            && let Some(group_owner) = merge(&self.group_owner, &other.group_owner)
+        {
+            return Some(Filter {
+                group_owner: Some(group_owner),
+                ..self.clone()
+            });
+        }
+
+        None
+    }
+
+    /// Checks an object owned by `uid` and `gid` with `permissions` against the filter.
+    ///
+    /// The user owner is checked first, then the group owner and the permissions last.
+    #[must_use]
+    pub fn matches(&self, uid: u32, gid: u32, permissions: &PermissionBlock) -> MatchOutcome {
+        for (field, expected, actual) in [
+            (OwnerField::User, &self.user_owner, uid),
+            (OwnerField::Group, &self.group_owner, gid),
+        ] {
+            if let Some(expected) = expected
+                && !expected.contains(actual)
+            {
+                return MatchOutcome::OwnerMismatch {
+                    field,
+                    expected: expected.clone(),
+                    actual,
+                };
+            }
+        }
+
+        if let Some(expected) = &self.permissions
+            && !expected.is_compatible(permissions)
+        {
+            return MatchOutcome::PermMismatch(PermissionDiff {
+                expected: expected.clone(),
+                actual: permissions.clone(),
+            });
+        }
+
+        MatchOutcome::Matched
+    }
+}
+
+/// Owner of an object compared by a filter.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum OwnerField {
+    User,
+    Group,
+}
+
+/// Permissions required by a filter next to the ones an object actually has.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PermissionDiff {
+    pub expected: PermissionBlock,
+    pub actual: PermissionBlock,
+}
+
+impl PermissionDiff {
+    /// Describes how `actual` differs from `expected` in `chmod` notation, like `g+w o-r` or
+    /// `u-w+x`.
+    ///
+    /// Wildcard bits of `expected` never differ.
+    #[must_use]
+    pub fn delta(&self) -> String {
+        let mut changes = Vec::new();
+
+        for (class, expected, actual) in [
+            ('u', &self.expected.user, &self.actual.user),
+            ('g', &self.expected.group, &self.actual.group),
+            ('o', &self.expected.other, &self.actual.other),
+        ] {
+            let mut change = String::new();
+
+            for (operator, from, to) in [
+                ('-', PermissionState::SET, PermissionState::UNSET),
+                ('+', PermissionState::UNSET, PermissionState::SET),
+            ] {
+                let bits: String = [
+                    (expected.read, actual.read, 'r'),
+                    (expected.write, actual.write, 'w'),
+                    (expected.execute, actual.execute, 'x'),
+                ]
+                .iter()
+                .filter(|(expected, actual, _)| *expected == from && *actual == to)
+                .map(|(_, _, bit)| bit)
+                .collect();
+
+                if !bits.is_empty() {
+                    change.push(operator);
+                    change.push_str(&bits);
+                }
+            }
+
+            if !change.is_empty() {
+                changes.push(format!("{class}{change}"));
+            }
+        }
+
+        changes.join(" ")
+    }
+}
+
+/// Lists every bit contradicting the filter, like `group w set, expected -`.
+impl Display for PermissionDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut mismatches = Vec::new();
+
+        for (expected, actual, bits) in [
+            (&self.expected.user, &self.actual.user, USER_BITS),
+            (&self.expected.group, &self.actual.group, GROUP_BITS),
+            (&self.expected.other, &self.actual.other, OTHER_BITS),
+            (&self.expected.special, &self.actual.special, SPECIAL_BITS),
+        ] {
+            for ((expected, actual), (name, symbol)) in
+                expected.states().into_iter().zip(actual.states()).zip(bits)
+            {
+                match (expected, actual) {
+                    (PermissionState::UNSET, PermissionState::SET) => {
+                        mismatches.push(format!("{name} set, expected -"));
+                    }
+                    (PermissionState::SET, PermissionState::UNSET) => {
+                        mismatches.push(format!("{name} unset, expected {symbol}"));
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        write!(f, "{}", mismatches.join("; "))
+    }
+}
+
+/// Names and symbols of the read, write and execute bit of each class, in [`PermissionDiff`]
+/// explanations.
+const USER_BITS: [(&str, char); 3] = [("user r", 'r'), ("user w", 'w'), ("user x", 'x')];
+const GROUP_BITS: [(&str, char); 3] = [("group r", 'r'), ("group w", 'w'), ("group x", 'x')];
+const OTHER_BITS: [(&str, char); 3] = [("other r", 'r'), ("other w", 'w'), ("other x", 'x')];
+const SPECIAL_BITS: [(&str, char); 3] = [("setuid", 's'), ("setgid", 's'), ("sticky", 't')];
+
+/// Result of checking an object against a [`Filter`] or a [`FilterSet`].
+#[derive(Debug, PartialEq, Clone)]
+pub enum MatchOutcome {
+    Matched,
+    OwnerMismatch {
+        field: OwnerField,
+        expected: IdMatch,
+        actual: u32,
+    },
+    PermMismatch(PermissionDiff),
+}
+
+impl MatchOutcome {
+    #[must_use]
+    pub fn is_match(&self) -> bool {
+        *self == MatchOutcome::Matched
+    }
+}
+
+impl Display for MatchOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MatchOutcome::Matched => write!(f, "matched"),
+            MatchOutcome::OwnerMismatch {
+                field,
+                expected,
+                actual,
+            } => {
+                let prefix = match field {
+                    OwnerField::User => 'u',
+                    OwnerField::Group => 'g',
+                };
+                write!(f, "owner {prefix}{actual}, expected {prefix}{expected}")
+            }
+            MatchOutcome::PermMismatch(diff) => match diff.delta() {
+                // Special bits aren't part of the delta
+                delta if delta.is_empty() => write!(f, "{diff}"),
+                delta => write!(f, "{delta}: {diff}"),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct FilterSet {
     pub filters: Vec<Filter>,
 }
@@ -217,56 +742,217 @@ impl FilterSet {
     fn add(&mut self, filter: Filter) {
         self.filters.push(filter);
     }
+
+    /// Checks an object against every filter of the set.
+    ///
+    /// Returns [`MatchOutcome::Matched`] as soon as a filter matches. Otherwise the mismatch
+    /// of the closest filter is returned, where a filter with matching owners is closer than
+    /// one with a different owner. An empty set doesn't constrain anything.
+    #[must_use]
+    pub fn matches(&self, uid: u32, gid: u32, permissions: &PermissionBlock) -> MatchOutcome {
+        let mut closest: Option<MatchOutcome> = None;
+
+        for filter in &self.filters {
+            let outcome = filter.matches(uid, gid, permissions);
+            match (&outcome, &closest) {
+                (MatchOutcome::Matched, _) => return outcome,
+                (MatchOutcome::PermMismatch(_), Some(MatchOutcome::OwnerMismatch { .. }))
+                | (_, None) => {
+                    closest = Some(outcome);
+                }
+                _ => {}
+            }
+        }
+
+        closest.unwrap_or(MatchOutcome::Matched)
+    }
+
+    /// Applies [`PermissionBlock::unset_as_wildcard`] to every filter of the set.
+    #[must_use]
+    pub fn unset_as_wildcard(&self) -> Self {
+        FilterSet {
+            filters: self
+                .filters
+                .iter()
+                .map(|filter| Filter {
+                    permissions: filter
+                        .permissions
+                        .as_ref()
+                        .map(PermissionBlock::unset_as_wildcard),
+                    ..filter.clone()
+                })
+                .collect(),
+        }
+    }
+
+    /// Constrains the group of every filter that names its user with `u:<NAME>` but has no
+    /// group to the primary group of that user. An explicit group takes precedence.
+    #[must_use]
+    pub fn imply_primary_group(&self) -> Self {
+        FilterSet {
+            filters: self
+                .filters
+                .iter()
+                .map(|filter| Filter {
+                    group_owner: filter
+                        .group_owner
+                        .clone()
+                        .or(filter.primary_group.map(IdMatch::Exact)),
+                    ..filter.clone()
+                })
+                .collect(),
+        }
+    }
+
+    /// Merges the overlapping or adjacent owner ranges of filters that are otherwise the
+    /// same, like `u1000-1500,u1400-2000` into `u1000-2000`. Parsing already does this.
+    #[must_use]
+    pub fn merge_ranges(&self) -> Self {
+        let mut filters: Vec<Filter> = Vec::new();
+
+        for filter in &self.filters {
+            let mut merged = filter.clone();
+            let mut position = filters.len();
+
+            // A merged range may reach a filter that the original one didn't
+            while let Some((index, with)) = filters
+                .iter()
+                .enumerate()
+                .find_map(|(index, kept)| kept.merged(&merged).map(|with| (index, with)))
+            {
+                filters.remove(index);
+                position = position.min(index);
+                merged = with;
+            }
+
+            filters.insert(position.min(filters.len()), merged);
+        }
+
+        FilterSet { filters }
+    }
+
+    /// Drops the permissions of every filter of the set, keeping only the owners.
+    #[must_use]
+    pub fn owners_only(&self) -> Self {
+        FilterSet {
+            filters: self
+                .filters
+                .iter()
+                .map(|filter| Filter {
+                    permissions: None,
+                    ..filter.clone()
+                })
+                .collect(),
+        }
+    }
 }
 
 impl FromStr for FilterSet {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> anyhow::Result<Self, Self::Err> {
+        FilterSet::parse(s, Accounts::load)
+    }
+}
+
+impl FilterSet {
+    /// Parses `s`, resolving user and group names with the accounts returned by `load_accounts`.
+    ///
+    /// The accounts are only loaded if a filter refers to a user or group by name (`u:alice`,
+    /// `g:wheel`). A name extends to the end of the filter or the next `:`, so `u:alice:g33`
+    /// also constrains the group.
+    fn parse<F>(s: &str, load_accounts: F) -> anyhow::Result<Self>
+    where
+        F: FnOnce() -> anyhow::Result<Accounts>,
+    {
         let mut filter_set: FilterSet = FilterSet::new();
+        let mut load_accounts = Some(load_accounts);
+        let mut accounts: Option<Accounts> = None;
+
+        let permissions_regex = regex::Regex::new(r"^((r|-|\*)(w|-|\*)(x|-|\*)){3}$")?;
+        let octal_regex = regex::Regex::new(r"^\d+")?;
+        let user_regex = regex::Regex::new(r"^.*u(\d+(?:-\d+|(?:\|\d+)+)?).*$")?;
+        let name_regex = regex::Regex::new(r"([ug]):([^:]+):?")?;
+        let group_regex = regex::Regex::new(r"^.*g(\d+(?:-\d+)?).*$")?;
+        let owners_regex = regex::Regex::new(r"^(u\d+(-\d+|(\|\d+)+)?|g\d+(-\d+)?)*$")?;
+
+        for filter in s.split(',').map(str::trim) {
+            let (user_name, group_name) = owner_names(&name_regex, filter)?;
+            let part = &*name_regex.replace_all(filter, "");
 
-        for part in s.split(',') {
+            // The permissions end where the owners start
+            let permissions_part = &part[..part.find(['u', 'g']).unwrap_or(part.len())];
             let permissions = {
-                if regex::Regex::new(r"^((r|-|\*)(w|-|\*)(x|-|\*)){3}")?.is_match(part) {
-                    let user = PartialPermissionBlock::safe_from_chars(
-                        part.get(..3)
-                            .ok_or(anyhow!("Failed to extract user permissions"))?,
-                    )?;
-                    let group = PartialPermissionBlock::safe_from_chars(
-                        part.get(3..6)
-                            .ok_or(anyhow!("Failed to extract group permissions"))?,
-                    )?;
-                    let other = PartialPermissionBlock::safe_from_chars(
-                        part.get(6..9)
-                            .ok_or(anyhow!("Failed to extract other permissions"))?,
-                    )?;
-
-                    Some(PermissionBlock { user, group, other })
-                } else {
-                    None
-                }
-            };
-            let user_owner =
-                if let Some(captures) = regex::Regex::new(r"^.*u(\d+).*$")?.captures(part) {
-                    if let Some(first_capture) = captures.get(1) {
-                        Some(first_capture.as_str().parse::<u32>()?)
-                    } else {
-                        None
+                if let Some(digits) = octal_regex.find(permissions_part) {
+                    let rest = &permissions_part[digits.end()..];
+                    if permissions_regex.is_match(rest) {
+                        bail!("Octal and symbolic permissions can't be combined in \"{part}\"");
                     }
-                } else {
-                    None
-                };
-            let group_owner =
-                if let Some(captures) = regex::Regex::new(r"^.*g(\d+).*$")?.captures(part) {
-                    if let Some(first_capture) = captures.get(1) {
-                        Some(first_capture.as_str().parse::<u32>()?)
-                    } else {
-                        None
+                    if !rest.is_empty() {
+                        bail!("Unexpected \"{rest}\" after the permissions in \"{part}\"");
                     }
-                } else {
+
+                    Some(PermissionBlock::parse_octal(digits.as_str())?)
+                } else if permissions_regex.is_match(permissions_part) {
+                    Some(PermissionBlock::parse_symbolic(permissions_part)?)
+                } else if let Some(symbolic) = permissions_part.get(..9)
+                    && permissions_regex.is_match(symbolic)
+                {
+                    let rest = &permissions_part[9..];
+                    if rest.starts_with(|char: char| char.is_ascii_digit()) {
+                        bail!("Octal and symbolic permissions can't be combined in \"{part}\"");
+                    }
+
+                    bail!("Unexpected \"{rest}\" after the permissions in \"{part}\"");
+                } else if permissions_part.is_empty() {
                     None
+                } else {
+                    bail!("Unrecognized filter component \"{filter}\"");
+                }
+            };
+            let owners_part = &part[permissions_part.len()..];
+            if !owners_regex.is_match(owners_part) {
+                bail!("Unrecognized filter component \"{filter}\"");
+            }
+            check_single_owners(owners_part, filter)?;
+            let owner = |regex: &regex::Regex| {
+                regex
+                    .captures(part)
+                    .map(|captures| IdMatch::parse(&captures[1], filter))
+                    .transpose()
+            };
+            let user_owner = owner(&user_regex)?;
+            let group_owner = owner(&group_regex)?;
+
+            let mut resolve = |name: Option<String>,
+                               has_id: bool,
+                               kind: &str,
+                               lookup: fn(&Accounts, &str) -> Option<u32>|
+             -> anyhow::Result<Option<u32>> {
+                let Some(name) = name else {
+                    return Ok(None);
                 };
 
+                if has_id {
+                    bail!("Filter \"{filter}\" specifies the {kind} both by id and by name");
+                }
+
+                if let Some(load) = load_accounts.take() {
+                    accounts = Some(load()?);
+                }
+
+                accounts
+                    .as_ref()
+                    .and_then(|accounts| lookup(accounts, &name))
+                    .map(Some)
+                    .ok_or(anyhow!("Unknown {kind} \"{name}\" in filter"))
+            };
+            let user_id = resolve(user_name, user_owner.is_some(), "user", Accounts::uid_of)?;
+            let user_owner = user_owner.or(user_id.map(IdMatch::Exact));
+            let group_id = resolve(group_name, group_owner.is_some(), "group", Accounts::gid_of)?;
+            let group_owner = group_owner.or(group_id.map(IdMatch::Exact));
+            let primary_group = user_id.and_then(|uid| accounts.as_ref()?.primary_gid_of(uid));
+
             if user_owner.is_none() && group_owner.is_none() && permissions.is_none() {
                 continue;
             }
@@ -275,6 +961,7 @@ impl FromStr for FilterSet {
                 user_owner,
                 group_owner,
                 permissions,
+                primary_group,
             });
         }
 
@@ -282,16 +969,58 @@ impl FromStr for FilterSet {
             bail!("No valid filter provided");
         }
 
-        Ok(filter_set)
+        Ok(filter_set.merge_ranges())
     }
 }
 
+/// Returns the user and group names of `filter`, as captured by `name_regex`.
+fn owner_names(
+    name_regex: &regex::Regex,
+    filter: &str,
+) -> anyhow::Result<(Option<String>, Option<String>)> {
+    let mut user_name = None;
+    let mut group_name = None;
+
+    for captures in name_regex.captures_iter(filter) {
+        let (name, kind) = match &captures[1] {
+            "u" => (&mut user_name, "user"),
+            _ => (&mut group_name, "group"),
+        };
+
+        if name.replace(captures[2].to_string()).is_some() {
+            bail!("Filter \"{filter}\" names more than one {kind}");
+        }
+    }
+
+    Ok((user_name, group_name))
+}
+
+/// Rejects `owners`, the owner part of `filter`, if it constrains the user or group twice.
+fn check_single_owners(owners: &str, filter: &str) -> anyhow::Result<()> {
+    for (token, kind) in [('u', "user"), ('g', "group")] {
+        if owners.matches(token).count() > 1 {
+            bail!("Filter \"{filter}\" specifies more than one {kind}");
+        }
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
+#[allow(clippy::too_many_lines)]
 mod test {
-    use std::str::FromStr;
+    use std::{
+        fs::{self, Permissions},
+        os::unix::fs::PermissionsExt,
+        str::FromStr,
+    };
 
-    use crate::input_parser::{
-        Filter, FilterSet, PartialPermissionBlock, PermissionBlock, PermissionState,
+    use crate::{
+        accounts::Accounts,
+        input_parser::{
+            Filter, FilterSet, IdMatch, MatchOutcome, OwnerField, PartialPermissionBlock,
+            PermissionBlock, PermissionDiff, PermissionState,
+        },
     };
 
     #[test]
@@ -375,8 +1104,10 @@ mod test {
                             read: PermissionState::UNSET,
                             write: PermissionState::UNSET,
                             execute: PermissionState::UNSET
-                        }
-                    })
+                        },
+                        special: PartialPermissionBlock::WILDCARD
+                    }),
+                    primary_group: None
                 }]
             }
         );
@@ -385,8 +1116,9 @@ mod test {
             FilterSet {
                 filters: vec![Filter {
                     user_owner: None,
-                    group_owner: Some(1000),
-                    permissions: None
+                    group_owner: Some(IdMatch::Exact(1000)),
+                    permissions: None,
+                    primary_group: None
                 }]
             }
         );
@@ -395,7 +1127,7 @@ mod test {
             FilterSet {
                 filters: vec![Filter {
                     user_owner: None,
-                    group_owner: Some(1000),
+                    group_owner: Some(IdMatch::Exact(1000)),
                     permissions: Some(PermissionBlock {
                         user: PartialPermissionBlock {
                             read: PermissionState::UNSET,
@@ -411,8 +1143,10 @@ mod test {
                             read: PermissionState::UNSET,
                             write: PermissionState::UNSET,
                             execute: PermissionState::UNSET
-                        }
-                    })
+                        },
+                        special: PartialPermissionBlock::WILDCARD
+                    }),
+                    primary_group: None
                 }]
             }
         );
@@ -420,8 +1154,8 @@ mod test {
             FilterSet::from_str("---------g1000u1000").unwrap(),
             FilterSet {
                 filters: vec![Filter {
-                    user_owner: Some(1000),
-                    group_owner: Some(1000),
+                    user_owner: Some(IdMatch::Exact(1000)),
+                    group_owner: Some(IdMatch::Exact(1000)),
                     permissions: Some(PermissionBlock {
                         user: PartialPermissionBlock {
                             read: PermissionState::UNSET,
@@ -437,8 +1171,10 @@ mod test {
                             read: PermissionState::UNSET,
                             write: PermissionState::UNSET,
                             execute: PermissionState::UNSET
-                        }
-                    })
+                        },
+                        special: PartialPermissionBlock::WILDCARD
+                    }),
+                    primary_group: None
                 }]
             }
         );
@@ -446,7 +1182,7 @@ mod test {
             FilterSet::from_str("---------u1000").unwrap(),
             FilterSet {
                 filters: vec![Filter {
-                    user_owner: Some(1000),
+                    user_owner: Some(IdMatch::Exact(1000)),
                     group_owner: None,
                     permissions: Some(PermissionBlock {
                         user: PartialPermissionBlock {
@@ -463,8 +1199,10 @@ mod test {
                             read: PermissionState::UNSET,
                             write: PermissionState::UNSET,
                             execute: PermissionState::UNSET
-                        }
-                    })
+                        },
+                        special: PartialPermissionBlock::WILDCARD
+                    }),
+                    primary_group: None
                 }]
             }
         );
@@ -543,4 +1281,565 @@ mod test {
     fn test_from_st_mode_digit_panic() {
         let _ = PartialPermissionBlock::from_st_mode_digit(8);
     }
+
+    #[test]
+    fn test_octal_filters() {
+        let parse = |filter: &str| FilterSet::from_str(filter).map(|set| set.filters[0].clone());
+
+        assert_eq!(
+            parse("755").unwrap().permissions,
+            FilterSet::from_str("rwxr-xr-x").unwrap().filters[0].permissions
+        );
+        assert_eq!(
+            parse("0755").unwrap().permissions,
+            Some(PermissionBlock::from_octal(0o755).unwrap())
+        );
+        assert_eq!(
+            parse("4755u0g33").unwrap(),
+            Filter {
+                user_owner: Some(IdMatch::Exact(0)),
+                group_owner: Some(IdMatch::Exact(33)),
+                permissions: Some(PermissionBlock::from_octal(0o4755).unwrap()),
+                primary_group: None,
+            }
+        );
+        assert_eq!(
+            parse("40").unwrap().permissions,
+            FilterSet::from_str("---r-----").unwrap().filters[0].permissions
+        );
+        assert_eq!(
+            FilterSet::from_str("u0,640").unwrap().filters[1].permissions,
+            FilterSet::from_str("rw-r-----").unwrap().filters[0].permissions
+        );
+
+        // 3 digits leave the special bits open, 4 digits constrain them
+        let setuid = PermissionBlock::from_mode_bits(0o4755);
+        assert!(
+            parse("755")
+                .unwrap()
+                .permissions
+                .unwrap()
+                .is_compatible(&setuid)
+        );
+        assert!(
+            parse("4755")
+                .unwrap()
+                .permissions
+                .unwrap()
+                .is_compatible(&setuid)
+        );
+        assert!(
+            !parse("0755")
+                .unwrap()
+                .permissions
+                .unwrap()
+                .is_compatible(&setuid)
+        );
+
+        assert!(parse("0758").is_err());
+        assert!(parse("9").is_err());
+        assert!(parse("07555").is_err());
+        assert!(parse("0755rwxr-xr-x").is_err());
+        assert!(parse("rwxr-xr-x0755").is_err());
+        assert!(PermissionBlock::from_octal(0o10000).is_err());
+
+        assert_eq!(parse("4755u0").unwrap().to_string(), "u0 4755");
+        assert_eq!(parse("755u0").unwrap().to_string(), "u0 rwxr-xr-x");
+        assert_eq!(
+            PermissionBlock::from_octal(0o2750).unwrap().to_octal(),
+            Some(0o2750)
+        );
+        assert_eq!(
+            parse("r-*------").unwrap().permissions.unwrap().to_octal(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_trailing_garbage() {
+        let err = FilterSet::from_str("rwxr-xr-xZ").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unexpected \"Z\" after the permissions in \"rwxr-xr-xZ\""
+        );
+        assert!(FilterSet::from_str("rwxr-xr-xJUNKu1000").is_err());
+        assert!(FilterSet::from_str("rw-------u1000,r--r--r--x").is_err());
+        assert!(FilterSet::from_str("750x").is_err());
+
+        let filter = &FilterSet::from_str("rwxr-xr-xu1000g33").unwrap().filters[0];
+        assert_eq!(filter.to_string(), "u1000 g33 rwxr-xr-x");
+    }
+
+    #[test]
+    fn test_unrecognized_components() {
+        let err = FilterSet::from_str("u0,uu1000").unwrap_err();
+        assert_eq!(err.to_string(), "Unrecognized filter component \"uu1000\"");
+        assert!(FilterSet::from_str("u1000x").is_err());
+        assert!(FilterSet::from_str("g1|2").is_err());
+        assert!(FilterSet::from_str("*").is_err());
+        assert!(FilterSet::from_str("user1000").is_err());
+
+        // Empty parts, like from a trailing comma, are still tolerated
+        assert_eq!(FilterSet::from_str("u1000,,g33,").unwrap().filters.len(), 2);
+        assert!(FilterSet::from_str(",").is_err());
+    }
+
+    #[test]
+    fn test_whitespace() {
+        assert_eq!(
+            FilterSet::from_str("u1000, g1000").unwrap(),
+            FilterSet::from_str("u1000,g1000").unwrap()
+        );
+        assert_eq!(
+            FilterSet::from_str(" rw-r--r--u0 ,\t750g33 , ").unwrap(),
+            FilterSet::from_str("rw-r--r--u0,750g33").unwrap()
+        );
+        assert!(FilterSet::from_str("rw-r--r-- u0").is_err());
+    }
+
+    #[test]
+    fn test_user_names() {
+        let accounts = || {
+            Ok(Accounts::parse(
+                "root:x:0:0:root:/root:/bin/bash\n\
+                 alice:x:1000:1000::/home/alice:/bin/sh\n\
+                 greg1:x:1001:1001::/home/greg1:/bin/sh\n",
+                "",
+            ))
+        };
+        // The primary groups are checked by test_imply_primary_group
+        let parse = |filter: &str| {
+            FilterSet::parse(filter, accounts).map(|mut filter_set| {
+                for filter in &mut filter_set.filters {
+                    filter.primary_group = None;
+                }
+                filter_set
+            })
+        };
+
+        assert_eq!(
+            parse("u:alice").unwrap(),
+            FilterSet::from_str("u1000").unwrap()
+        );
+        assert_eq!(
+            parse("rw-------u:alice:g33,750u0,u:root").unwrap(),
+            FilterSet::from_str("rw-------u1000g33,750u0,u0").unwrap()
+        );
+        assert_eq!(
+            parse("u:greg1").unwrap(),
+            FilterSet::from_str("u1001").unwrap()
+        );
+
+        let err = parse("rw-------u:mallory").unwrap_err();
+        assert_eq!(err.to_string(), "Unknown user \"mallory\" in filter");
+        assert!(parse("u1000u:alice").is_err());
+        assert!(parse("u:alice:u:root").is_err());
+    }
+
+    #[test]
+    fn test_group_names() {
+        let accounts = || {
+            Ok(Accounts::parse(
+                "alice:x:1000:1000::/home/alice:/bin/sh\n",
+                "wheel:x:10:alice\n\
+                 kvmu:x:78:\n\
+                 users:x:100:\n",
+            ))
+        };
+        // The primary groups are checked by test_imply_primary_group
+        let parse = |filter: &str| {
+            FilterSet::parse(filter, accounts).map(|mut filter_set| {
+                for filter in &mut filter_set.filters {
+                    filter.primary_group = None;
+                }
+                filter_set
+            })
+        };
+
+        assert_eq!(
+            parse("g:wheel").unwrap(),
+            FilterSet::from_str("g10").unwrap()
+        );
+        assert_eq!(
+            parse("rw-r-----u:alice:g:wheel,g:kvmu:u0,u1000g:users").unwrap(),
+            FilterSet::from_str("rw-r-----u1000g10,u0g78,u1000g100").unwrap()
+        );
+
+        let err = parse("g:docker").unwrap_err();
+        assert_eq!(err.to_string(), "Unknown group \"docker\" in filter");
+        assert!(parse("g10g:wheel").is_err());
+
+        // The accounts are only needed for names
+        assert!(FilterSet::parse("u1000", || anyhow::bail!("unavailable")).is_ok());
+        assert!(FilterSet::parse("u:alice", || anyhow::bail!("unavailable")).is_err());
+    }
+
+    #[test]
+    fn test_imply_primary_group() {
+        let accounts = || {
+            Ok(Accounts::parse(
+                "alice:x:1000:100::/home/alice:/bin/sh\n\
+                 bob:x:1001:1001::/home/bob:/bin/sh\n",
+                "wheel:x:10:alice\n",
+            ))
+        };
+        let implied = |filter: &str| {
+            FilterSet::parse(filter, accounts)
+                .unwrap()
+                .imply_primary_group()
+        };
+        let owners = |filter_set: FilterSet| {
+            filter_set
+                .filters
+                .iter()
+                .map(|filter| (filter.user_owner.clone(), filter.group_owner.clone()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            owners(implied("u:alice,750u:bob")),
+            owners(FilterSet::from_str("u1000g100,750u1001g1001").unwrap())
+        );
+        // An explicit group takes precedence, numeric uids aren't looked up
+        assert_eq!(
+            owners(implied("u:alice:g33,u:alice:g:wheel,u1000")),
+            owners(FilterSet::from_str("u1000g33,u1000g10,u1000").unwrap())
+        );
+        // Without the option the group stays unconstrained
+        assert_eq!(
+            FilterSet::parse("u:alice", accounts).unwrap().filters[0].group_owner,
+            None
+        );
+    }
+
+    #[test]
+    fn test_uid_ranges() {
+        let filter = &FilterSet::from_str("rw-------u1000-60000g33")
+            .unwrap()
+            .filters[0];
+        assert_eq!(filter.user_owner, Some(IdMatch::Range(1000, 60000)));
+        assert_eq!(filter.group_owner, Some(IdMatch::Exact(33)));
+        assert_eq!(filter.to_string(), "u1000-60000 g33 rw-------");
+
+        let rw = filter.permissions.clone().unwrap();
+        assert!(filter.matches(1000, 33, &rw).is_match());
+        assert!(filter.matches(60000, 33, &rw).is_match());
+        assert_eq!(
+            filter.matches(999, 33, &rw).to_string(),
+            "owner u999, expected u1000-60000"
+        );
+
+        assert_eq!(
+            FilterSet::from_str("u0").unwrap().filters[0].user_owner,
+            Some(IdMatch::Exact(0))
+        );
+        assert_eq!(
+            FilterSet::from_str("u5-5").unwrap().filters[0].user_owner,
+            Some(IdMatch::Range(5, 5))
+        );
+
+        let err = FilterSet::from_str("u2000-1000").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Range 2000-1000 in filter \"u2000-1000\" ends before it starts"
+        );
+    }
+
+    #[test]
+    fn test_gid_ranges() {
+        let filter = &FilterSet::from_str("u0g100-200").unwrap().filters[0];
+        assert_eq!(filter.user_owner, Some(IdMatch::Exact(0)));
+        assert_eq!(filter.group_owner, Some(IdMatch::Range(100, 200)));
+        assert_eq!(filter.to_string(), "u0 g100-200");
+
+        let any = PermissionBlock::from_mode_bits(0o644);
+        assert!(filter.matches(0, 150, &any).is_match());
+        assert_eq!(
+            filter.matches(0, 201, &any).to_string(),
+            "owner g201, expected g100-200"
+        );
+
+        assert_eq!(
+            FilterSet::from_str("u1000-60000g0-999").unwrap().filters[0],
+            Filter {
+                user_owner: Some(IdMatch::Range(1000, 60000)),
+                group_owner: Some(IdMatch::Range(0, 999)),
+                permissions: None,
+                primary_group: None,
+            }
+        );
+
+        let err = FilterSet::from_str("rw-------g200-100").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Range 200-100 in filter \"rw-------g200-100\" ends before it starts"
+        );
+    }
+
+    #[test]
+    fn test_merge_ranges() {
+        let owners = |filter: &str| {
+            FilterSet::from_str(filter)
+                .unwrap()
+                .filters
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        };
+
+        // Overlapping
+        assert_eq!(owners("u1000-1500,u1400-2000"), ["u1000-2000"]);
+        assert_eq!(
+            owners("rw-------u0g100-300,rw-------u0g150"),
+            ["u0 g100-300 rw-------"]
+        );
+        // Adjacent
+        assert_eq!(owners("u1000-1499,u1500-2000"), ["u1000-2000"]);
+        assert_eq!(owners("u999,u1000-2000g33"), ["u999", "u1000-2000 g33"]);
+        assert_eq!(owners("u999g33,u1000-2000g33"), ["u999-2000 g33"]);
+        assert_eq!(owners("u1-2,u5-6,u0,u3-4"), ["u0-6"]);
+        assert_eq!(
+            owners("u4294967295,u4294967290-4294967294"),
+            ["u4294967290-4294967295"]
+        );
+
+        // Only otherwise equivalent filters with ranges are merged
+        assert_eq!(owners("u1-2,u4-5"), ["u1-2", "u4-5"]);
+        assert_eq!(owners("u1,u2"), ["u1", "u2"]);
+        assert_eq!(owners("u2|3,u3-4"), ["u2|3", "u3-4"]);
+        assert_eq!(
+            owners("rw-------u1000-1500,r--------u1400-2000"),
+            ["u1000-1500 rw-------", "u1400-2000 r--------"]
+        );
+        assert_eq!(owners("u1-2g0,u3-4g1"), ["u1-2 g0", "u3-4 g1"]);
+    }
+
+    #[test]
+    fn test_uid_sets() {
+        let filters = FilterSet::from_str("rw-------u1000|1001|1002g33,u0").unwrap();
+        let filter = &filters.filters[0];
+        assert_eq!(
+            filter.user_owner,
+            Some(IdMatch::AnyOf(vec![1000, 1001, 1002]))
+        );
+        assert_eq!(filter.group_owner, Some(IdMatch::Exact(33)));
+        assert_eq!(filter.to_string(), "u1000|1001|1002 g33 rw-------");
+        assert_eq!(filters.filters[1].user_owner, Some(IdMatch::Exact(0)));
+
+        let rw = filter.permissions.clone().unwrap();
+        assert!(filter.matches(1001, 33, &rw).is_match());
+        assert_eq!(
+            filter.matches(1003, 33, &rw).to_string(),
+            "owner u1003, expected u1000|1001|1002"
+        );
+        assert!(!filter.matches(1001, 34, &rw).is_match());
+
+        let err = FilterSet::from_str("u1000u2000").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Filter \"u1000u2000\" specifies more than one user"
+        );
+        assert!(FilterSet::from_str("rw-------g1u0g2").is_err());
+    }
+
+    #[test]
+    fn test_unset_as_wildcard() {
+        let strict = FilterSet::from_str("r--------").unwrap();
+        let lenient = strict.unset_as_wildcard();
+        let permissions = PermissionBlock::from_mode_bits;
+        let strict_block = strict.filters[0].permissions.as_ref().unwrap();
+        let lenient_block = lenient.filters[0].permissions.as_ref().unwrap();
+
+        assert_eq!(lenient_block.to_string(), "r********");
+
+        assert!(strict_block.is_compatible(&permissions(0o400)));
+        assert!(!strict_block.is_compatible(&permissions(0o755)));
+        assert!(!strict_block.is_compatible(&permissions(0o000)));
+
+        assert!(lenient_block.is_compatible(&permissions(0o400)));
+        assert!(lenient_block.is_compatible(&permissions(0o755)));
+        assert!(!lenient_block.is_compatible(&permissions(0o000)));
+
+        // Unset special bits stay enforced
+        let octal = FilterSet::from_str("0644").unwrap().unset_as_wildcard();
+        let octal_block = octal.filters[0].permissions.as_ref().unwrap();
+        assert!(octal_block.is_compatible(&permissions(0o755)));
+        assert!(!octal_block.is_compatible(&permissions(0o4755)));
+    }
+
+    #[test]
+    fn test_other_exceeds_group() {
+        let permissions = PermissionBlock::from_mode_bits;
+
+        assert!(permissions(0o646).other_exceeds_group());
+        assert!(permissions(0o601).other_exceeds_group());
+        assert!(!permissions(0o664).other_exceeds_group());
+        assert!(!permissions(0o644).other_exceeds_group());
+        assert!(!permissions(0o600).other_exceeds_group());
+        assert!(!permissions(0o777).other_exceeds_group());
+    }
+
+    #[test]
+    fn test_permission_state_bit_ops() {
+        use PermissionState::{SET, UNSET, WILDCARD};
+
+        for (lhs, rhs, and, or) in [
+            (SET, SET, SET, SET),
+            (SET, UNSET, UNSET, SET),
+            (SET, WILDCARD, SET, SET),
+            (UNSET, SET, UNSET, SET),
+            (UNSET, UNSET, UNSET, UNSET),
+            (UNSET, WILDCARD, UNSET, UNSET),
+            (WILDCARD, SET, SET, SET),
+            (WILDCARD, UNSET, UNSET, UNSET),
+            (WILDCARD, WILDCARD, WILDCARD, WILDCARD),
+        ] {
+            assert_eq!(lhs & rhs, and, "{lhs:?} & {rhs:?}");
+            assert_eq!(lhs | rhs, or, "{lhs:?} | {rhs:?}");
+        }
+    }
+
+    #[test]
+    fn test_permission_block_bit_ops() {
+        let block = |s: &str| {
+            FilterSet::from_str(s).unwrap().filters[0]
+                .permissions
+                .clone()
+                .unwrap()
+        };
+
+        assert_eq!(
+            PartialPermissionBlock::safe_from_chars("rw*").unwrap()
+                & PartialPermissionBlock::safe_from_chars("r-x").unwrap(),
+            PartialPermissionBlock::safe_from_chars("r-x").unwrap()
+        );
+        assert_eq!(
+            PartialPermissionBlock::safe_from_chars("-w*").unwrap()
+                | PartialPermissionBlock::safe_from_chars("r-*").unwrap(),
+            PartialPermissionBlock::safe_from_chars("rw*").unwrap()
+        );
+
+        assert_eq!(
+            (block("rwxr-x***") & block("rw-rw-r--")).to_string(),
+            "rw-r--r--"
+        );
+        assert_eq!(
+            (block("rwxr-x***") | block("rw-rw-r--")).to_string(),
+            "rwxrwxr--"
+        );
+    }
+
+    #[test]
+    fn test_permission_diff_delta() {
+        let diff = |expected: u32, actual: u32| PermissionDiff {
+            expected: PermissionBlock::from_mode_bits(expected),
+            actual: PermissionBlock::from_mode_bits(actual),
+        };
+
+        assert_eq!(diff(0o644, 0o664).delta(), "g+w");
+        assert_eq!(diff(0o644, 0o640).delta(), "o-r");
+        assert_eq!(diff(0o750, 0o705).delta(), "g-rx o+rx");
+        assert_eq!(diff(0o600, 0o500).delta(), "u-w+x");
+        assert_eq!(diff(0o640, 0o501).delta(), "u-w+x g-r o+x");
+        assert_eq!(diff(0o644, 0o644).delta(), "");
+
+        let wildcard = PermissionDiff {
+            expected: FilterSet::from_str("rw-r--***").unwrap().filters[0]
+                .permissions
+                .clone()
+                .unwrap(),
+            actual: PermissionBlock::from_mode_bits(0o667),
+        };
+        assert_eq!(wildcard.delta(), "g+w");
+    }
+
+    #[test]
+    fn test_match_outcome() {
+        let permissions = |value: &str| {
+            FilterSet::from_str(value).unwrap().filters[0]
+                .permissions
+                .clone()
+                .unwrap()
+        };
+        let rw_r = permissions("rw-r-----");
+
+        let filter = &FilterSet::from_str("rw-r--***u1000g33").unwrap().filters[0];
+        assert_eq!(filter.matches(1000, 33, &rw_r), MatchOutcome::Matched);
+        assert_eq!(
+            filter.matches(0, 33, &rw_r),
+            MatchOutcome::OwnerMismatch {
+                field: OwnerField::User,
+                expected: IdMatch::Exact(1000),
+                actual: 0
+            }
+        );
+        assert_eq!(
+            filter.matches(1000, 0, &rw_r),
+            MatchOutcome::OwnerMismatch {
+                field: OwnerField::Group,
+                expected: IdMatch::Exact(33),
+                actual: 0
+            }
+        );
+        assert_eq!(
+            filter.matches(1000, 33, &permissions("rwxr-----")),
+            MatchOutcome::PermMismatch(PermissionDiff {
+                expected: permissions("rw-r--***"),
+                actual: permissions("rwxr-----"),
+            })
+        );
+
+        let filters = FilterSet::from_str("u0,rw-------u1000").unwrap();
+        assert!(filters.matches(0, 0, &rw_r).is_match());
+        assert_eq!(
+            filters.matches(1000, 0, &rw_r).to_string(),
+            "g+r: group r set, expected -"
+        );
+        assert_eq!(
+            filters.matches(33, 0, &rw_r).to_string(),
+            "owner u33, expected u0"
+        );
+        assert!(
+            FilterSet { filters: vec![] }
+                .matches(0, 0, &rw_r)
+                .is_match()
+        );
+
+        let diff = PermissionDiff {
+            expected: PermissionBlock::from_mode_bits(0o4644),
+            actual: PermissionBlock::from_mode_bits(0o0460),
+        };
+        assert_eq!(
+            diff.to_string(),
+            "user w unset, expected w; group w set, expected -; other r unset, expected r; \
+             setuid unset, expected s"
+        );
+    }
+
+    #[test]
+    fn test_from_mode_bits_ignores_type() {
+        assert_eq!(
+            PermissionBlock::from_mode_bits(0o100_750),
+            PermissionBlock::from_mode_bits(0o040_750)
+        );
+        assert_eq!(
+            PermissionBlock::from_mode_bits(0o120_750),
+            PermissionBlock::from_mode_bits(0o750)
+        );
+        assert_ne!(
+            PermissionBlock::from_mode_bits(0o100_750),
+            PermissionBlock::from_mode_bits(0o100_755)
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("file");
+        fs::write(&file, "").unwrap();
+        fs::set_permissions(&file, Permissions::from_mode(0o750)).unwrap();
+        fs::set_permissions(dir.path(), Permissions::from_mode(0o750)).unwrap();
+
+        assert_eq!(
+            PermissionBlock::from(&fs::metadata(&file).unwrap()),
+            PermissionBlock::from(&fs::metadata(dir.path()).unwrap())
+        );
+    }
 }
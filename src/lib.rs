@@ -1,21 +1,347 @@
 use std::{
-    fs::{self, Metadata},
-    os::linux::fs::MetadataExt,
-    path::Path,
+    collections::HashMap,
+    env,
+    ffi::OsStr,
+    fs::{self, DirEntry, File, Metadata},
+    io::{self, BufWriter, Write},
+    os::{linux::fs::MetadataExt, unix::ffi::OsStrExt},
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, mpsc},
+    thread,
+    time::{Duration, Instant},
 };
 
+use accounts::{Accounts, can_write};
+use anyhow::{anyhow, bail};
 use cli::Args;
-use input_parser::{Filter, FilterSet, PermissionBlock};
-use util::print_access_error;
+use config::ScanConfig;
+use globset::GlobSetBuilder;
+use input_parser::{Filter, FilterSet, IdMatch, PermissionBlock};
+use output::{
+    CSV_HEADER, Decision, FlatPermissions, ObjectKind, OutputFormat, RUST_SNIPPET_IMPORTS,
+    SortOrder, Template, TraceRecord, ViolationRecord, csv_record, find_command, mode_bits,
+    rust_snippet,
+};
+use presets::{builtin_profile, default_presets_file, load_policy, load_preset};
+use special_bits::{
+    OTHER_WRITE, breaks_setgid_inheritance, is_setgid_root, is_setuid_root,
+    is_sticky_world_writable,
+};
+use stats::{AGE_BUCKETS, ScanStats};
+use util::{
+    git_last_author, is_device_directory, is_device_node, is_mount_root, is_sampled,
+    normalize_path, path_hash, print_access_error, print_warning, shuffle, symlink_chain,
+    symlink_target_type, unix_now,
+};
 
+pub mod accounts;
 pub mod cli;
+pub mod config;
 pub mod input_parser;
+pub mod output;
+pub mod presets;
+pub mod special_bits;
+pub mod stats;
 pub mod util;
 
-pub fn run(config: &Args) -> anyhow::Result<()> {
+/// Result of a completed search.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Outcome {
+    /// Number of flagged objects
+    pub flagged: u64,
+    /// Number of objects that couldn't be examined, so findings may be missing
+    pub access_errors: u64,
+}
+
+impl Outcome {
+    #[must_use]
+    pub fn from_stats(stats: &ScanStats) -> Self {
+        Self {
+            flagged: stats.flagged,
+            access_errors: stats.access_errors,
+        }
+    }
+
+    /// Outcome of a check that flagged `flagged` objects without access errors.
+    #[must_use]
+    pub fn flagged(flagged: usize) -> Self {
+        Self {
+            flagged: flagged as u64,
+            access_errors: 0,
+        }
+    }
+
+    /// Exit status of the process: 0 if clean, 1 for violations and 2 for access errors.
+    #[must_use]
+    pub fn exit_code(self) -> i32 {
+        match (self.access_errors, self.flagged) {
+            (0, 0) => 0,
+            (0, _) => 1,
+            _ => 2,
+        }
+    }
+}
+
+/// Result of checking a single object with [`check_single`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckResult {
+    Pass,
+    /// Passes because the object is never reported, with the reason
+    Excluded(&'static str),
+    /// Flagged, with the reason
+    Fail(String),
+}
+
+/// Object flagged by a search, as returned by [`scan`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    pub path: PathBuf,
+    pub permissions: PermissionBlock,
+    pub uid: u32,
+    pub gid: u32,
+    pub kind: ObjectKind,
+    /// Symlink the object was reached through with `--report-under-target`
+    pub via_symlink: Option<PathBuf>,
+}
+
+impl Violation {
+    #[must_use]
+    pub fn new(path: &Path, metadata: &Metadata, is_symlink: bool) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            permissions: PermissionBlock::from(metadata),
+            uid: metadata.st_uid(),
+            gid: metadata.st_gid(),
+            kind: ObjectKind::from_metadata(metadata, is_symlink),
+            via_symlink: None,
+        }
+    }
+}
+
+/// Searches the base directory like [`run`], but returns the flagged objects instead of
+/// printing anything.
+///
+/// Objects that can't be examined, like directories that can't be listed, are skipped
+/// without reporting the error, unless the error budget or `fail_fast_on_error` aborts the
+/// search. Use [`violations`] to get these errors too. The output and summary options of
+/// `config` are ignored.
+///
+/// # Errors
+///
+/// Returns an error if the base directory doesn't exist, its metadata can't be read or the
+/// search is aborted.
+pub fn scan(config: &ScanConfig) -> anyhow::Result<Vec<Violation>> {
+    let stats = library_walk(config, |config| {
+        config.collect_violations = true;
+        config.defer_errors = true;
+    })?;
+
+    Ok(stats.violations)
+}
+
+/// Searches the base directory like [`scan`], but yields each flagged object as soon as it's
+/// found.
+///
+/// The search runs on a separate thread that pauses once [`VIOLATION_BUFFER`] items wait for
+/// the consumer and stops when the iterator is dropped. Objects that can't be examined are
+/// yielded as `Err` items, as is a final error if the search can't start or is aborted.
+pub fn violations(config: &ScanConfig) -> impl Iterator<Item = anyhow::Result<Violation>> + use<> {
+    let (sender, receiver) = mpsc::sync_channel(VIOLATION_BUFFER);
+    let config = config.clone();
+
+    thread::spawn(move || {
+        let result = library_walk(&config, |config| {
+            config.violation_sender = Some(sender.clone());
+        });
+
+        if let Err(err) = result {
+            // Fails only if the iterator was dropped, so nobody is left to tell
+            let _ = sender.send(Err(err));
+        }
+    });
+
+    receiver.into_iter()
+}
+
+/// Number of findings [`violations`] searches ahead of its consumer.
+pub const VIOLATION_BUFFER: usize = 64;
+
+/// Runs the search behind [`scan`] and [`violations`], after `prepare` chose how findings and
+/// access errors are passed on.
+#[allow(clippy::unnecessary_debug_formatting)]
+fn library_walk(
+    config: &ScanConfig,
+    prepare: impl FnOnce(&mut ScanConfig),
+) -> anyhow::Result<ScanStats> {
+    if !config.base_dir.exists() {
+        bail!("Base directory {:?} doesn't exist", config.base_dir);
+    }
+
+    let mut config = config.clone();
+    prepare(&mut config);
+
+    let mut stats = ScanStats::default();
+    run_recursive(
+        &config,
+        &config.base_dir,
+        &config.base_dir.metadata()?,
+        0,
+        &mut stats,
+    )?;
+
+    Ok(stats)
+}
+
+/// Search configured from library code, without going through the command line.
+///
+/// ```no_run
+/// use permsearch::Scanner;
+///
+/// let violations = Scanner::new("/srv/www")
+///     .file_filter("rw-r--r--".parse()?)
+///     .ignore_symlinks(true)
+///     .run()?;
+/// # anyhow::Ok(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Scanner {
+    config: ScanConfig,
+}
+
+impl Scanner {
+    #[must_use]
+    pub fn new(base_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            config: ScanConfig::new(base_dir),
+        }
+    }
+
+    #[must_use]
+    pub fn base_dir(mut self, base_dir: impl Into<PathBuf>) -> Self {
+        self.config.base_dir = base_dir.into();
+        self
+    }
+
+    /// Allowed directory types, directories aren't checked without one.
+    #[must_use]
+    pub fn directory_filter(mut self, filter: FilterSet) -> Self {
+        self.config.directory_filter = Some(filter);
+        self
+    }
+
+    /// Allowed file types, files aren't checked without one.
+    #[must_use]
+    pub fn file_filter(mut self, filter: FilterSet) -> Self {
+        self.config.file_filter = Some(filter);
+        self
+    }
+
+    #[must_use]
+    pub fn ignore_symlinks(mut self, ignore: bool) -> Self {
+        self.config.ignore_symlinks = ignore;
+        self
+    }
+
+    /// Runs the search, see [`scan`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the base directory doesn't exist or its metadata can't be read.
+    pub fn run(&self) -> anyhow::Result<Vec<Violation>> {
+        scan(&self.config)
+    }
+}
+
+/// Starts from a complete configuration, for the options without a builder method.
+impl From<ScanConfig> for Scanner {
+    fn from(config: ScanConfig) -> Self {
+        Self { config }
+    }
+}
+
+/// Runs a complete search as configured by `config`.
+///
+/// # Errors
+///
+/// Returns an error if the base directory doesn't exist (unless `--allow-missing-base` is
+/// given), the filter preset can't be loaded or the metadata of the base directory can't be
+/// read.
+#[allow(clippy::unnecessary_debug_formatting)]
+pub fn run(config: &Args) -> anyhow::Result<Outcome> {
+    if config.emit_rust {
+        emit_rust(config)?;
+        return Ok(Outcome::default());
+    }
+
+    if config.emit_find {
+        emit_find(config)?;
+        return Ok(Outcome::default());
+    }
+
+    if config.check_path_dirs {
+        let list = match &config.path_list {
+            Some(list) => list.clone(),
+            None => env::var_os("PATH").ok_or(anyhow!("$PATH isn't set"))?,
+        };
+
+        let findings = path_dir_findings(&list);
+        for (path, reason) in &findings {
+            println!("FAIL {path:?}: {reason}");
+        }
+
+        return Ok(Outcome::flagged(findings.len()));
+    }
+
+    if !config.base_dir.exists() {
+        if config.allow_missing_base {
+            print_warning(&format!(
+                "Base directory {:?} doesn't exist, nothing to search",
+                config.base_dir
+            ));
+            return Ok(Outcome::default());
+        }
+
+        bail!("Base directory {:?} doesn't exist", config.base_dir);
+    }
+
+    if config.home_dirs {
+        let findings = home_dir_findings(&config.base_dir, &Accounts::load()?)?;
+        for (path, reason) in &findings {
+            println!("FAIL {path:?}: {reason}");
+        }
+
+        return Ok(Outcome::flagged(findings.len()));
+    }
+
+    if let Some(path) = &config.check_path {
+        match check_single(config, path)? {
+            CheckResult::Pass => println!("PASS {path:?}"),
+            CheckResult::Excluded(reason) => println!("PASS {path:?} (excluded: {reason})"),
+            CheckResult::Fail(reason) => {
+                println!("FAIL {path:?}: {reason}");
+                return Ok(Outcome::flagged(1));
+            }
+        }
+
+        return Ok(Outcome::default());
+    }
+
+    let compare_policy = config.compare_policy.as_deref();
+    let mut config = effective_config(config)?;
+    open_trace(&mut config)?;
+    let config = &config;
+
     let basedir_meta = &config.base_dir.metadata()?;
 
-    if !config.silent {
+    if config.csv {
+        println!("{}", csv_record(CSV_HEADER)?);
+    } else if !config.silent
+        && !config.count
+        && !config.print0
+        && config.template.is_none()
+        && config.format == OutputFormat::Human
+    {
         println!("Base directory: {:?}", config.base_dir);
 
         if config.file_filter.is_none() && config.directory_filter.is_none() {
@@ -28,149 +354,3119 @@ pub fn run(config: &Args) -> anyhow::Result<()> {
         } else {
             if let Some(filter) = &config.directory_filter {
                 for single_filter in &filter.filters {
-                    println!("Allowed  (dir): {}", single_filter);
+                    println!("Allowed  (dir): {single_filter}");
                 }
             }
 
             if let Some(filter) = &config.file_filter {
                 for single_filter in &filter.filters {
-                    println!("Allowed (file): {}", single_filter);
+                    println!("Allowed (file): {single_filter}");
                 }
             }
         }
 
-        println!("");
+        println!();
+    }
+
+    let mut stats = ScanStats::default();
+
+    let started = Instant::now();
+    let result = run_recursive(config, &config.base_dir, basedir_meta, 0, &mut stats);
+    let total = started.elapsed();
+
+    // Also flushed after an aborted search, which is when the trace is most useful
+    flush_trace(config)?;
+    if result.is_err() {
+        print_deferred_errors(&stats);
+    }
+    result?;
+
+    if config.count {
+        println!("{}", stats.flagged);
+    }
+
+    print_summaries(config, compare_policy, &stats, total);
+    print_deferred_errors(&stats);
+
+    Ok(Outcome::from_stats(&stats))
+}
+
+/// Prints the access errors held back by `--defer-errors`.
+fn print_deferred_errors(stats: &ScanStats) {
+    if stats.deferred_errors.is_empty() {
+        return;
+    }
+
+    eprintln!();
+    for msg in &stats.deferred_errors {
+        print_access_error(msg);
     }
+}
 
-    run_recursive(config, &config.base_dir, basedir_meta)?;
+/// Creates the `--trace-json` file the traversal decisions are written to.
+#[allow(clippy::unnecessary_debug_formatting)]
+fn open_trace(config: &mut ScanConfig) -> anyhow::Result<()> {
+    if let Some(path) = &config.trace_json {
+        let file = File::create(path)
+            .map_err(|err| anyhow!("Failed to create trace file {path:?}: {err}"))?;
+        config.trace_writer = Some(Arc::new(Mutex::new(BufWriter::new(file))));
+    }
 
     Ok(())
 }
 
-pub fn run_recursive(
-    config: &Args,
-    current_path: &Path,
-    base_dir_meta: &Metadata,
-) -> anyhow::Result<()> {
-    let current_meta = current_path.metadata()?;
-    check_object(current_path, config, base_dir_meta, false)?;
+/// Writes the traversal decisions still buffered to the `--trace-json` file.
+#[allow(clippy::unnecessary_debug_formatting)]
+fn flush_trace(config: &ScanConfig) -> anyhow::Result<()> {
+    if let Some(writer) = &config.trace_writer {
+        writer
+            .lock()
+            .map_err(|_| anyhow!("Trace file writer poisoned"))?
+            .flush()
+            .map_err(|err| anyhow!("Failed to write trace file {:?}: {err}", config.trace_json))?;
+    }
 
-    if current_meta.is_dir() {
-        let children = match fs::read_dir(current_path) {
-            Ok(value) => value,
-            Err(err) => {
-                print_access_error(&format!("accessing {:?}: {err}", current_path));
-                return Ok(());
-            }
-        };
+    Ok(())
+}
 
-        for child in children {
-            match child {
-                Ok(value) => {
-                    if value.path().is_symlink() {
-                        if !config.ignore_symlinks {
-                            if let Err(err) =
-                                check_object(&value.path(), config, base_dir_meta, true)
-                            {
-                                print_access_error(&format!(
-                                    "reading symlink {:?}: {}. The symlink might be broken.",
-                                    value.path(),
-                                    err
-                                ));
-                            }
-                        }
-                        continue;
-                    }
-                    run_recursive(config, &value.path(), base_dir_meta)
-                }
-                Err(err) => {
-                    print_access_error(&format!("accessing child of {:?}: {err}", current_path));
-                    continue;
-                }
-            }?;
+/// Writes a traversal decision to the `--trace-json` file.
+#[allow(clippy::unnecessary_debug_formatting)]
+fn trace(
+    config: &ScanConfig,
+    stats: &mut ScanStats,
+    decision: Decision,
+    path: Option<&Path>,
+    reason: Option<&str>,
+) {
+    let Some(writer) = &config.trace_writer else {
+        return;
+    };
+
+    let record = TraceRecord {
+        decision,
+        path: path.map(|path| path.to_string_lossy().to_string()),
+        reason: reason.map(ToString::to_string),
+    };
+    let written = match writer.lock() {
+        Ok(mut writer) => serde_json::to_writer(&mut *writer, &record)
+            .map_err(io::Error::from)
+            .and_then(|()| writer.write_all(b"\n")),
+        Err(_) => return,
+    };
+
+    if let Err(err) = written
+        && !stats.trace_failed
+    {
+        print_warning(&format!(
+            "Failed to write trace file {:?}: {err}",
+            config.trace_json
+        ));
+        stats.trace_failed = true;
+    }
+}
+
+/// Prints Rust code building the effective filters with this crate.
+fn emit_rust(config: &Args) -> anyhow::Result<()> {
+    let config = effective_config(config)?;
+
+    println!("{RUST_SNIPPET_IMPORTS}");
+    for (name, filter) in [
+        ("directory_filter", &config.directory_filter),
+        ("file_filter", &config.file_filter),
+        ("report_filter", &config.report_filter),
+    ] {
+        if let Some(filter) = filter {
+            println!("\nlet {name} = {};", rust_snippet(filter));
         }
     }
 
     Ok(())
 }
 
-fn check_object(
-    path: &Path,
-    config: &Args,
-    base_dir_meta: &Metadata,
-    is_symlink: bool,
-) -> anyhow::Result<()> {
-    let metadata = path.metadata()?;
-    let is_dir = metadata.is_dir();
+/// Prints a `find` command listing the objects that match none of the effective filters.
+fn emit_find(config: &Args) -> anyhow::Result<()> {
+    let config = effective_config(config)?;
 
-    if is_dir && config.directory_filter.is_none() {
-        return Ok(());
+    if config.directory_filter.is_none() && config.file_filter.is_none() {
+        bail!("--emit-find needs a directory or file filter");
     }
 
-    if metadata.is_file() && config.file_filter.is_none() {
-        return Ok(());
+    for (is_set, option) in [
+        (
+            config.setgid_inheritance_check,
+            "--setgid-inheritance-check",
+        ),
+        (
+            config.other_not_exceeding_group,
+            "--other-not-exceeding-group",
+        ),
+        (
+            config.exclude_sticky_world_writable,
+            "--exclude-sticky-world-writable",
+        ),
+        (!config.exclude_set.is_empty(), "--exclude"),
+        (
+            config.min_uid.is_some() || config.max_uid.is_some(),
+            "--min-uid/--max-uid",
+        ),
+        (config.skip_empty_files, "--skip-empty-files"),
+        (config.report_filter.is_some(), "--report-filter"),
+        (!config.object_types.is_empty(), "--type"),
+        (config.created_newer_than.is_some(), "--created-newer-than"),
+        (!config.cross_devices.is_empty(), "--cross-into"),
+    ] {
+        if is_set {
+            print_warning(&format!(
+                "{option} isn't supported by --emit-find, ignoring it"
+            ));
+        }
     }
 
-    let permissions = PermissionBlock::from(&metadata);
+    let find_options: Vec<String> = [
+        config.max_depth.map(|depth| format!("-maxdepth {depth}")),
+        config.min_depth.map(|depth| format!("-mindepth {depth}")),
+        config.one_file_system.then(|| "-xdev".to_string()),
+        config.newer_than.map(|time| format!("-newermt @{time}")),
+        config
+            .older_than
+            .map(|time| format!("! -newermt @{}", time.saturating_sub(1))),
+        config
+            .min_size
+            .and_then(|size| size.checked_sub(1))
+            .map(|size| format!("-size +{size}c")),
+        config
+            .max_size
+            .and_then(|size| size.checked_add(1))
+            .map(|size| format!("-size -{size}c")),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
 
-    let filters = match if is_dir {
-        &config.directory_filter
+    println!(
+        "{}",
+        find_command(
+            &config.base_dir,
+            &find_options,
+            config.directory_filter.as_ref(),
+            config.file_filter.as_ref()
+        )
+    );
+
+    Ok(())
+}
+
+/// Prints the requested summaries of a finished search to stderr, `compare_policy` is the
+/// `--compare-policy` file.
+fn print_summaries(
+    config: &ScanConfig,
+    compare_policy: Option<&Path>,
+    stats: &ScanStats,
+    total: Duration,
+) {
+    if !config.silent {
+        eprintln!("{}", stats.summary());
+
+        if limit_reached(config, stats) {
+            eprintln!("Stopped after {} findings (--limit)", stats.flagged);
+        }
+    }
+
+    if config.scanned_by_type {
+        eprintln!("{}", stats.scanned_summary());
+    }
+
+    if config.types_summary {
+        eprintln!("{}", stats.types_summary());
+    }
+
+    if config.permission_histogram {
+        eprintln!("{}", stats.mode_histogram_summary());
+    }
+
+    if let Some(count) = config.worst_dirs {
+        eprintln!("Directories with the most findings:");
+        for (directory, flagged) in stats.worst_directories(count) {
+            eprintln!("{flagged: >7} {}", directory.to_string_lossy());
+        }
+    }
+
+    if config.owner_inconsistency {
+        eprintln!("Modes shared by several owners:");
+        for (directory, mode, owners) in stats.owner_inconsistencies() {
+            eprintln!(
+                "{} {mode:04o}: {}",
+                directory.to_string_lossy(),
+                owners
+                    .iter()
+                    .map(|uid| format!("u{uid}"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+    }
+
+    if config.age_buckets {
+        eprintln!("Findings by modification time:");
+        for ((_, label), flagged) in AGE_BUCKETS.iter().zip(stats.flagged_per_age) {
+            eprintln!("{label: >7} {flagged}");
+        }
+    }
+
+    if config.by_extension {
+        eprintln!("Findings by extension:");
+        for (extension, flagged) in stats.extension_distribution() {
+            match extension {
+                Some(extension) => eprintln!("{flagged: >7} .{extension}"),
+                None => eprintln!("{flagged: >7} (none)"),
+            }
+        }
+    }
+
+    if let Some(ratio) = config.sample {
+        eprintln!(
+            "Sampled 1/{ratio}: {} flagged, ~{} estimated in total",
+            stats.flagged,
+            stats.flagged * u64::from(ratio)
+        );
+    }
+
+    if let Some(path) = compare_policy {
+        eprintln!("{}", stats.policy_comparison.summary(path));
+    }
+
+    if config.profile {
+        eprintln!("{}", stats.profile.summary(total));
+    }
+}
+
+/// Checks only `path` like a search would examine it, including the exclusions like
+/// `--min-size` or `--type`.
+///
+/// # Errors
+///
+/// Returns an error if the filter preset can't be loaded or the metadata of `path` or the
+/// base directory can't be read.
+pub fn check_single(config: &Args, path: &Path) -> anyhow::Result<CheckResult> {
+    let config = &effective_config(config)?;
+    let base_dir_meta = config.base_dir.metadata()?;
+    let is_symlink = path.is_symlink();
+    let metadata = if is_symlink {
+        path.symlink_metadata()?
     } else {
-        &config.file_filter
-    } {
-        Some(value) => value,
-        None => &FilterSet {
-            filters: vec![Filter {
-                user_owner: Some(base_dir_meta.st_uid()),
-                group_owner: Some(base_dir_meta.st_gid()),
-                permissions: None,
-            }],
-        },
+        path.metadata()?
     };
+    if let Some(reason) = exclusion(
+        config,
+        &mut ScanStats::default(),
+        path,
+        &metadata,
+        is_symlink,
+    ) {
+        return Ok(CheckResult::Excluded(reason));
+    }
 
-    let meta_uid = metadata.st_uid();
-    let meta_gid: u32 = metadata.st_gid();
+    let parent_meta = parent_metadata(config, path)?;
+
+    Ok(
+        match evaluate_object(
+            config,
+            &base_dir_meta,
+            &metadata,
+            parent_meta.as_ref(),
+            is_symlink,
+        ) {
+            Some(reason) => CheckResult::Fail(reason),
+            None => CheckResult::Pass,
+        },
+    )
+}
 
-    for filter in &filters.filters {
-        if let Some(filter_uid) = filter.user_owner {
-            if filter_uid != meta_uid {
+/// Audits the directories of the colon-separated search path `list` and their entries.
+///
+/// Returns the objects that are world-writable or not owned by root, with the reasons.
+/// Directories that can't be read are skipped with a warning.
+#[must_use]
+#[allow(clippy::unnecessary_debug_formatting)]
+pub fn path_dir_findings(list: &OsStr) -> Vec<(PathBuf, String)> {
+    let mut findings = Vec::new();
+
+    for dir in env::split_paths(list).filter(|dir| !dir.as_os_str().is_empty()) {
+        let (metadata, entries) = match (dir.metadata(), fs::read_dir(&dir)) {
+            (Ok(metadata), Ok(entries)) => (metadata, entries),
+            (Err(err), _) | (_, Err(err)) => {
+                print_warning(&format!("Skipping search path directory {dir:?}: {err}"));
                 continue;
             }
+        };
+
+        if let Some(reason) = path_dir_issues(&metadata) {
+            findings.push((dir.clone(), reason));
         }
 
-        if let Some(filter_gid) = filter.group_owner {
-            if filter_gid != meta_gid {
-                continue;
+        let mut entries: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .collect();
+        entries.sort();
+
+        for entry in entries {
+            // Follows symlinks, their own mode is always 0777
+            if let Ok(metadata) = entry.metadata()
+                && let Some(reason) = path_dir_issues(&metadata)
+            {
+                findings.push((entry, reason));
             }
         }
+    }
 
-        if let Some(filter_permissions) = &filter.permissions {
-            if !filter_permissions.is_compatible(&permissions) {
-                continue;
-            }
+    findings
+}
+
+/// Audits the home directories directly below `base`, sorted by path.
+///
+/// Returns the directories that aren't owned by the user of the same name or are readable or
+/// writable by group or others, with the reasons.
+///
+/// # Errors
+///
+/// Returns an error if `base` can't be listed.
+pub fn home_dir_findings(
+    base: &Path,
+    accounts: &Accounts,
+) -> anyhow::Result<Vec<(PathBuf, String)>> {
+    let mut homes: Vec<PathBuf> = fs::read_dir(base)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| !path.is_symlink() && path.is_dir())
+        .collect();
+    homes.sort();
+
+    let mut findings = Vec::new();
+
+    for home in homes {
+        let Ok(metadata) = home.metadata() else {
+            continue;
+        };
+        let name = home
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let mut issues = Vec::new();
+
+        match accounts.uid_of(&name) {
+            Some(uid) if uid == metadata.st_uid() => {}
+            Some(uid) => issues.push(format!(
+                "owned by u{}, but {name} is u{uid}",
+                metadata.st_uid()
+            )),
+            None => issues.push(format!("no user called {name}")),
+        }
+        if metadata.st_mode() & 0o066 != 0 {
+            issues.push("accessible by group or others".to_string());
         }
 
-        // Filter applies completely
-        return Ok(());
+        if !issues.is_empty() {
+            findings.push((home, issues.join(", ")));
+        }
+    }
+
+    Ok(findings)
+}
+
+fn path_dir_issues(metadata: &Metadata) -> Option<String> {
+    let mut issues = Vec::new();
+
+    if metadata.st_mode() & OTHER_WRITE != 0 {
+        issues.push("world-writable".to_string());
+    }
+    if metadata.st_uid() != 0 {
+        issues.push(format!("owned by u{}", metadata.st_uid()));
+    }
+
+    (!issues.is_empty()).then(|| issues.join(", "))
+}
+
+/// Reads the metadata of the directory containing `path` if a check needs it.
+fn parent_metadata(config: &ScanConfig, path: &Path) -> anyhow::Result<Option<Metadata>> {
+    if !config.setgid_inheritance_check {
+        return Ok(None);
     }
 
-    let prefix = if is_symlink {
-        "l"
+    let Some(parent) = path.parent() else {
+        return Ok(None);
+    };
+    let parent = if parent.as_os_str().is_empty() {
+        Path::new(".")
     } else {
-        if is_dir {
-            "d"
-        } else {
-            "-"
-        }
+        parent
     };
 
-    println!(
-        "{prefix}{} {: >5} {: >5} {}",
-        permissions,
-        meta_uid,
-        meta_gid,
-        path.to_string_lossy()
-    );
+    Ok(Some(parent.metadata()?))
+}
 
-    Ok(())
+/// Resolves the filter preset and filter modifiers of `args` into the configuration used for
+/// the search.
+#[allow(clippy::unnecessary_debug_formatting)]
+fn effective_config(args: &Args) -> anyhow::Result<ScanConfig> {
+    let mut config = ScanConfig::from(args);
+
+    if args.normalize_base {
+        config.base_dir = normalize_path(&config.base_dir);
+    }
+
+    if let Some(name) = &args.filter_preset {
+        let presets_file = match &args.presets_file {
+            Some(path) => path.clone(),
+            None => default_presets_file().ok_or(anyhow!("No presets file location found"))?,
+        };
+        let preset = load_preset(&presets_file, name, &args.filter_comment_prefix)?;
+
+        config.directory_filter = config.directory_filter.or(preset.directory_filter);
+        config.file_filter = config.file_filter.or(preset.file_filter);
+    }
+
+    if let Some(name) = &args.profile_name {
+        let profile = builtin_profile(name)?;
+
+        config.directory_filter = config.directory_filter.or(profile.directory_filter);
+        config.file_filter = config.file_filter.or(profile.file_filter);
+    }
+
+    if let Some(uid) = config.effective_write_for {
+        config.effective_write_gids = Accounts::load()?.gids_of(uid);
+    }
+
+    if args.imply_primary_group {
+        for filter in [
+            &mut config.directory_filter,
+            &mut config.file_filter,
+            &mut config.report_filter,
+        ] {
+            *filter = filter.as_ref().map(FilterSet::imply_primary_group);
+        }
+    }
+
+    if args.wildcard_unspecified {
+        for filter in [
+            &mut config.directory_filter,
+            &mut config.file_filter,
+            &mut config.report_filter,
+        ] {
+            *filter = filter.as_ref().map(FilterSet::unset_as_wildcard);
+        }
+    }
+
+    if !args.exclude.is_empty() {
+        let mut builder = GlobSetBuilder::new();
+        for glob in &args.exclude {
+            builder.add(glob.clone());
+        }
+        config.exclude_set = builder.build()?;
+    }
+
+    config.cross_devices = args
+        .cross_into
+        .iter()
+        .map(|path| {
+            path.metadata()
+                .map(|metadata| metadata.st_dev())
+                .map_err(|err| anyhow!("Failed to read --cross-into path {path:?}: {err}"))
+        })
+        .collect::<anyhow::Result<_>>()?;
+
+    config.colorize = args.color.enabled();
+
+    if let Some(path) = &args.template_file {
+        let template = fs::read_to_string(path)
+            .map_err(|err| anyhow!("Failed to read template file {path:?}: {err}"))?;
+        config.template = Some(Template::parse(&template)?);
+    }
+
+    if let Some(path) = &args.compare_policy {
+        let policy = load_policy(path, &args.filter_comment_prefix)?;
+        let mut compare_config = config.clone();
+
+        compare_config.directory_filter = policy.directory_filter;
+        compare_config.file_filter = policy.file_filter;
+        if args.wildcard_unspecified {
+            for filter in [
+                &mut compare_config.directory_filter,
+                &mut compare_config.file_filter,
+            ] {
+                *filter = filter.as_ref().map(FilterSet::unset_as_wildcard);
+            }
+        }
+
+        config.compare_config = Some(Box::new(compare_config));
+    }
+
+    Ok(config)
+}
+
+/// Checks `current_path` and, if it is a directory, all of its children.
+///
+/// # Errors
+///
+/// Returns an error if the search is aborted by `--fail-fast-on-error` or the error budget.
+#[allow(clippy::unnecessary_debug_formatting)]
+pub fn run_recursive(
+    config: &ScanConfig,
+    current_path: &Path,
+    base_dir_meta: &Metadata,
+    depth: usize,
+    stats: &mut ScanStats,
+) -> anyhow::Result<()> {
+    let started = Instant::now();
+    let current_meta = current_path.metadata();
+    stats.profile.metadata += started.elapsed();
+    let current_meta = match current_meta {
+        Ok(value) => value,
+        Err(err) if is_name_too_long(&err) => return long_path(config, stats, current_path),
+        Err(err) => {
+            return access_error(
+                config,
+                stats,
+                current_path,
+                &format!("accessing {current_path:?}: {err}"),
+            );
+        }
+    };
+
+    if limit_reached(config, stats)
+        || is_excluded_directory(config, current_path, &current_meta, base_dir_meta, stats)
+    {
+        return Ok(());
+    }
+
+    if is_deep_enough(config, depth) {
+        check_object(current_path, config, base_dir_meta, false, stats)?;
+    }
+
+    if current_meta.is_dir() && config.max_depth.is_none_or(|max_depth| depth < max_depth) {
+        trace(config, stats, Decision::Enter, Some(current_path), None);
+
+        let at_safe_mount_root = config.safe_root
+            && current_path == config.base_dir
+            && is_mount_root(current_path).unwrap_or(false);
+
+        let started = Instant::now();
+        let children = fs::read_dir(current_path).map(Iterator::collect::<Vec<_>>);
+        stats.profile.read_dir += started.elapsed();
+
+        let mut children = match children {
+            Ok(value) => value,
+            Err(err) if is_name_too_long(&err) => return long_path(config, stats, current_path),
+            Err(err) => {
+                return access_error(
+                    config,
+                    stats,
+                    current_path,
+                    &read_dir_error(current_path, &err),
+                );
+            }
+        };
+
+        // A fixed order keeps the output, and a shuffle, independent of the filesystem
+        if !config.no_sort {
+            sort_entries(config, &mut children);
+        }
+
+        if let Some(seed) = config.shuffle_seed {
+            shuffle(&mut children, seed ^ path_hash(current_path));
+        }
+
+        for child in children {
+            if limit_reached(config, stats) {
+                break;
+            }
+
+            match child {
+                Ok(value) => {
+                    let path = value.path();
+                    if config.strict_utf8_paths && value.file_name().to_str().is_none() {
+                        access_error(
+                            config,
+                            stats,
+                            &path,
+                            &format!("non-UTF-8 file name {path:?}"),
+                        )?;
+                        continue;
+                    }
+
+                    if is_excluded_path(config, &path) {
+                        let reason = Some("matches --exclude");
+                        trace(config, stats, Decision::Excluded, Some(&path), reason);
+                        continue;
+                    }
+
+                    if at_safe_mount_root && is_device_directory(&value.file_name()) {
+                        continue;
+                    }
+
+                    if at_safe_mount_root && value.file_type().is_ok_and(|t| is_device_node(&t)) {
+                        if is_deep_enough(config, depth + 1) {
+                            check_device_node(config, &value, base_dir_meta, stats)?;
+                        }
+                        continue;
+                    }
+
+                    if path.is_symlink() {
+                        visit_symlink(config, &path, base_dir_meta, depth + 1, stats)?;
+                        continue;
+                    }
+                    run_recursive(config, &path, base_dir_meta, depth + 1, stats)
+                }
+                Err(err) => {
+                    access_error(
+                        config,
+                        stats,
+                        current_path,
+                        &format!("accessing child of {current_path:?}: {err}"),
+                    )?;
+                    continue;
+                }
+            }?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Sorts the entries of a directory by the `--sort-by` key, names ignoring case with
+/// `--sort-case-insensitive`.
+fn sort_entries(config: &ScanConfig, entries: &mut [io::Result<DirEntry>]) {
+    match config.sort_by {
+        SortOrder::Name if config.sort_case_insensitive => {
+            entries.sort_by_cached_key(|entry| {
+                let name = entry.as_ref().ok()?.file_name();
+                Some((name.as_bytes().to_ascii_lowercase(), name))
+            });
+        }
+        SortOrder::Name => {
+            entries.sort_by_cached_key(|entry| entry.as_ref().ok().map(DirEntry::file_name));
+        }
+        SortOrder::Inode => entries.sort_by_cached_key(|entry| {
+            let metadata = entry.as_ref().ok()?.metadata().ok()?;
+            Some((metadata.st_dev(), metadata.st_ino()))
+        }),
+    }
+}
+
+/// Returns whether the `--limit` of findings was reached, which ends the search.
+fn limit_reached(config: &ScanConfig, stats: &ScanStats) -> bool {
+    config.limit.is_some_and(|limit| stats.flagged >= limit)
+}
+
+/// Checks the symlink at `path`, `depth` levels below the base directory, or searches the
+/// directory it points to with `--follow-symlinks`.
+#[allow(clippy::unnecessary_debug_formatting)]
+fn visit_symlink(
+    config: &ScanConfig,
+    path: &Path,
+    base_dir_meta: &Metadata,
+    depth: usize,
+    stats: &mut ScanStats,
+) -> anyhow::Result<()> {
+    if config.follow_symlinks && path.is_dir() {
+        // Symlinks may lead back into a directory that was already searched
+        if path.metadata().is_ok_and(|metadata| {
+            stats
+                .visited_directories
+                .contains(&(metadata.st_dev(), metadata.st_ino()))
+        }) {
+            return Ok(());
+        }
+
+        if config.report_under_target {
+            let target = match path.canonicalize() {
+                Ok(target) => target,
+                Err(err) => {
+                    return access_error(
+                        config,
+                        stats,
+                        path,
+                        &format!("resolving {path:?}: {err}"),
+                    );
+                }
+            };
+
+            let outer_symlink = stats.via_symlink.replace(path.to_path_buf());
+            let result = run_recursive(config, &target, base_dir_meta, depth, stats);
+            stats.via_symlink = outer_symlink;
+            return result;
+        }
+
+        return run_recursive(config, path, base_dir_meta, depth, stats);
+    }
+
+    if config.ignore_symlinks {
+        trace(config, stats, Decision::SkipSymlink, Some(path), None);
+        return Ok(());
+    }
+
+    if is_deep_enough(config, depth)
+        && let Err(err) = check_object(path, config, base_dir_meta, true, stats)
+    {
+        access_error(
+            config,
+            stats,
+            path,
+            &format!("reading symlink {path:?}: {err}"),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Returns whether `path` is skipped because it's on another filesystem that isn't crossed
+/// into. When following symlinks, also remembers that `path` is searched.
+fn is_excluded_directory(
+    config: &ScanConfig,
+    path: &Path,
+    metadata: &Metadata,
+    base_dir_meta: &Metadata,
+    stats: &mut ScanStats,
+) -> bool {
+    if config.one_file_system
+        && metadata.st_dev() != base_dir_meta.st_dev()
+        && !config.cross_devices.contains(&metadata.st_dev())
+    {
+        let reason = Some("on another filesystem");
+        trace(config, stats, Decision::Excluded, Some(path), reason);
+        return true;
+    }
+
+    // Only symlinks are skipped if they lead to a visited directory, so a symlink searched
+    // first doesn't hide the directory it points to
+    if config.follow_symlinks && metadata.is_dir() {
+        stats
+            .visited_directories
+            .insert((metadata.st_dev(), metadata.st_ino()));
+    }
+
+    false
+}
+
+/// Returns whether `path`, relative to the base directory, matches an `--exclude` pattern.
+fn is_excluded_path(config: &ScanConfig, path: &Path) -> bool {
+    !config.exclude_set.is_empty()
+        && config
+            .exclude_set
+            .is_match(path.strip_prefix(&config.base_dir).unwrap_or(path))
+}
+
+/// Returns whether objects `depth` levels below the base directory are checked.
+fn is_deep_enough(config: &ScanConfig, depth: usize) -> bool {
+    config.min_depth.is_none_or(|min_depth| depth >= min_depth)
+}
+
+/// Returns whether `err` is `ENAMETOOLONG`.
+fn is_name_too_long(err: &io::Error) -> bool {
+    err.kind() == io::ErrorKind::InvalidFilename
+}
+
+/// Handles a `path` too long to be examined, an access error unless `--skip-long-paths` is
+/// given.
+#[allow(clippy::unnecessary_debug_formatting)]
+fn long_path(config: &ScanConfig, stats: &mut ScanStats, path: &Path) -> anyhow::Result<()> {
+    if config.skip_long_paths {
+        trace(
+            config,
+            stats,
+            Decision::Excluded,
+            Some(path),
+            Some("path too long"),
+        );
+        return Ok(());
+    }
+
+    access_error(
+        config,
+        stats,
+        path,
+        &format!(
+            "path too long ({} bytes), skipping {path:?}",
+            path.as_os_str().len()
+        ),
+    )
+}
+
+/// Describes why `path` couldn't be listed, naming its owners if access was denied.
+#[allow(clippy::unnecessary_debug_formatting)]
+fn read_dir_error(path: &Path, err: &io::Error) -> String {
+    if err.kind() == io::ErrorKind::PermissionDenied
+        && let Ok(metadata) = path.symlink_metadata()
+    {
+        return format!(
+            "accessing {path:?}: {err} (owned by u{} g{})",
+            metadata.st_uid(),
+            metadata.st_gid()
+        );
+    }
+
+    format!("accessing {path:?}: {err}")
+}
+
+/// Prints an access error for `path` and aborts the search once the error budget is exceeded.
+fn access_error(
+    config: &ScanConfig,
+    stats: &mut ScanStats,
+    path: &Path,
+    msg: &str,
+) -> anyhow::Result<()> {
+    if let Some(sender) = &config.violation_sender {
+        sender
+            .send(Err(anyhow!("{msg}")))
+            .map_err(|_| anyhow!("Stopping, the violations are no longer read"))?;
+    } else if config.defer_errors {
+        stats.deferred_errors.push(msg.to_owned());
+    } else {
+        print_access_error(msg);
+    }
+    trace(config, stats, Decision::Error, Some(path), Some(msg));
+    stats.access_errors += 1;
+
+    if config.fail_fast_on_error {
+        bail!("Aborting after the first access error");
+    }
+
+    if let Some(budget) = config.error_budget
+        && stats.access_errors > budget
+    {
+        bail!(
+            "Aborting after {} access errors (error budget: {budget})",
+            stats.access_errors
+        );
+    }
+
+    Ok(())
+}
+
+fn check_object(
+    path: &Path,
+    config: &ScanConfig,
+    base_dir_meta: &Metadata,
+    is_symlink: bool,
+    stats: &mut ScanStats,
+) -> anyhow::Result<()> {
+    guard_panic(config, stats, path, |stats| {
+        examine_object(path, config, base_dir_meta, is_symlink, stats)
+    })
+}
+
+/// Checks the device node `entry` by the metadata of the entry itself, without resolving its
+/// path again.
+#[allow(clippy::unnecessary_debug_formatting)]
+fn check_device_node(
+    config: &ScanConfig,
+    entry: &DirEntry,
+    base_dir_meta: &Metadata,
+    stats: &mut ScanStats,
+) -> anyhow::Result<()> {
+    let path = entry.path();
+
+    guard_panic(config, stats, &path, |stats| {
+        if !is_sampled_object(config, stats, &path) {
+            return Ok(());
+        }
+
+        let started = Instant::now();
+        let metadata = entry.metadata();
+        stats.profile.metadata += started.elapsed();
+        match metadata {
+            Ok(metadata) => examine_metadata(&path, config, base_dir_meta, &metadata, false, stats),
+            Err(err) => access_error(config, stats, &path, &format!("accessing {path:?}: {err}")),
+        }
+    })
+}
+
+/// Runs `check` for `path`, turning a panic into an access error with `--continue-on-panic`.
+#[allow(clippy::unnecessary_debug_formatting)]
+fn guard_panic<F>(
+    config: &ScanConfig,
+    stats: &mut ScanStats,
+    path: &Path,
+    check: F,
+) -> anyhow::Result<()>
+where
+    F: FnOnce(&mut ScanStats) -> anyhow::Result<()>,
+{
+    if !config.continue_on_panic {
+        return check(stats);
+    }
+
+    match panic::catch_unwind(AssertUnwindSafe(|| check(stats))) {
+        Ok(result) => result,
+        Err(payload) => {
+            let reason = payload
+                .downcast_ref::<&str>()
+                .map(ToString::to_string)
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown cause".to_string());
+
+            access_error(
+                config,
+                stats,
+                path,
+                &format!("checking {path:?} panicked: {reason}"),
+            )
+        }
+    }
+}
+
+#[allow(clippy::unnecessary_debug_formatting)]
+fn examine_object(
+    path: &Path,
+    config: &ScanConfig,
+    base_dir_meta: &Metadata,
+    is_symlink: bool,
+    stats: &mut ScanStats,
+) -> anyhow::Result<()> {
+    if !is_sampled_object(config, stats, path) {
+        return Ok(());
+    }
+
+    let started = Instant::now();
+    let metadata = if is_symlink {
+        path.symlink_metadata()
+    } else {
+        path.metadata()
+    };
+    stats.profile.metadata += started.elapsed();
+    let metadata = match metadata {
+        Ok(value) => value,
+        Err(err) if is_name_too_long(&err) => return long_path(config, stats, path),
+        // Symlink errors are reported by the caller, which knows the link might be broken
+        Err(err) if is_symlink => return Err(err.into()),
+        Err(err) => {
+            return access_error(config, stats, path, &format!("accessing {path:?}: {err}"));
+        }
+    };
+
+    examine_metadata(path, config, base_dir_meta, &metadata, is_symlink, stats)
+}
+
+/// Returns whether `path` is part of the `--sample`, tracing it as excluded otherwise.
+fn is_sampled_object(config: &ScanConfig, stats: &mut ScanStats, path: &Path) -> bool {
+    if let Some(ratio) = config.sample
+        && !is_sampled(path, ratio)
+    {
+        trace(
+            config,
+            stats,
+            Decision::Excluded,
+            Some(path),
+            Some("not sampled"),
+        );
+        return false;
+    }
+
+    true
+}
+
+/// Checks the object at `path` with the already read `metadata`.
+fn examine_metadata(
+    path: &Path,
+    config: &ScanConfig,
+    base_dir_meta: &Metadata,
+    metadata: &Metadata,
+    is_symlink: bool,
+    stats: &mut ScanStats,
+) -> anyhow::Result<()> {
+    stats.count_scanned(metadata, is_symlink);
+
+    if config.permission_histogram {
+        stats.count_mode(metadata);
+    }
+
+    if config.owner_inconsistency && !is_symlink && path != config.base_dir {
+        stats.count_owner(path, metadata);
+    }
+
+    if let Some(reason) = exclusion(config, stats, path, metadata, is_symlink) {
+        trace(config, stats, Decision::Excluded, Some(path), Some(reason));
+        return Ok(());
+    }
+
+    let parent_meta = parent_metadata(config, path)?;
+    let violation = evaluate_object(
+        config,
+        base_dir_meta,
+        metadata,
+        parent_meta.as_ref(),
+        is_symlink,
+    );
+
+    if let Some(compare_config) = &config.compare_config {
+        let other_violation = evaluate_object(
+            compare_config,
+            base_dir_meta,
+            metadata,
+            parent_meta.as_ref(),
+            is_symlink,
+        );
+
+        stats
+            .policy_comparison
+            .count(violation.is_some(), other_violation.is_some());
+    }
+
+    if let Some(reason) = &violation {
+        trace(config, stats, Decision::Flagged, Some(path), Some(reason));
+
+        let started = Instant::now();
+        let result = report_object(path, config, metadata, is_symlink, stats);
+        stats.profile.output += started.elapsed();
+        result?;
+    }
+
+    Ok(())
+}
+
+/// Returns why an examined object is never reported, regardless of the filters.
+#[allow(clippy::unnecessary_debug_formatting)]
+fn exclusion(
+    config: &ScanConfig,
+    stats: &mut ScanStats,
+    path: &Path,
+    metadata: &Metadata,
+    is_symlink: bool,
+) -> Option<&'static str> {
+    if let Some(max_age) = config.created_newer_than {
+        match metadata.created() {
+            Ok(created) => {
+                if created.elapsed().is_ok_and(|age| age >= max_age) {
+                    return Some("created too long ago");
+                }
+            }
+            Err(err) => {
+                if !stats.creation_time_unsupported {
+                    print_warning(&format!(
+                        "Ignoring --created-newer-than, no creation time for {path:?}: {err}"
+                    ));
+                    stats.creation_time_unsupported = true;
+                }
+            }
+        }
+    }
+
+    if !config.object_types.is_empty()
+        && !config
+            .object_types
+            .contains(&ObjectKind::from_metadata(metadata, is_symlink))
+    {
+        return Some("other object type");
+    }
+
+    if config.skip_empty_files && !is_symlink && metadata.is_file() && metadata.len() == 0 {
+        return Some("empty file");
+    }
+
+    let modified = metadata.st_mtime();
+    if config.newer_than.is_some_and(|time| modified <= time)
+        || config.older_than.is_some_and(|time| modified >= time)
+    {
+        return Some("modified outside the time window");
+    }
+
+    let size = metadata.st_size();
+    if config.min_size.is_some_and(|min_size| size < min_size)
+        || config.max_size.is_some_and(|max_size| size > max_size)
+    {
+        return Some("size out of bounds");
+    }
+
+    let uid = metadata.st_uid();
+    if config.min_uid.is_some_and(|min_uid| uid < min_uid)
+        || config.max_uid.is_some_and(|max_uid| uid > max_uid)
+    {
+        return Some("uid out of bounds");
+    }
+
+    None
+}
+
+/// Describes the directories from the base directory down to the parent of `path`, caching
+/// their metadata in `cache`.
+fn parent_chain(
+    config: &ScanConfig,
+    path: &Path,
+    cache: &mut HashMap<PathBuf, (u32, u32, u32)>,
+) -> anyhow::Result<Vec<String>> {
+    let mut ancestors: Vec<&Path> = path
+        .ancestors()
+        .skip(1)
+        .take_while(|ancestor| ancestor.starts_with(&config.base_dir))
+        .collect();
+    ancestors.reverse();
+
+    ancestors
+        .into_iter()
+        .map(|ancestor| {
+            let (mode, uid, gid) = if let Some(value) = cache.get(ancestor) {
+                *value
+            } else {
+                let metadata = ancestor.metadata()?;
+                let value = (metadata.st_mode(), metadata.st_uid(), metadata.st_gid());
+                cache.insert(ancestor.to_path_buf(), value);
+                value
+            };
+
+            Ok(format!(
+                "d{} {uid: >5} {gid: >5} {}",
+                config.mode_display.render(mode),
+                config.quote_paths.quote(ancestor)
+            ))
+        })
+        .collect()
+}
+
+/// Decides if an object is flagged and returns the reason if it is.
+#[allow(clippy::similar_names)]
+fn evaluate_object(
+    config: &ScanConfig,
+    base_dir_meta: &Metadata,
+    metadata: &Metadata,
+    parent_meta: Option<&Metadata>,
+    is_symlink: bool,
+) -> Option<String> {
+    let is_dir = metadata.is_dir();
+
+    if config.exclude_sticky_world_writable && is_sticky_world_writable(metadata.st_mode()) {
+        return None;
+    }
+
+    if config.suid_root || config.sgid_root {
+        if !is_symlink && metadata.is_file() {
+            if config.suid_root && is_setuid_root(metadata.st_mode(), metadata.st_uid()) {
+                return Some("setuid root".to_string());
+            }
+
+            if config.sgid_root && is_setgid_root(metadata.st_mode(), metadata.st_gid()) {
+                return Some("setgid root".to_string());
+            }
+        }
+
+        return None;
+    }
+
+    if let Some(uid) = config.effective_write_for {
+        // Writing through a symlink writes to its target
+        return (!is_symlink
+            && can_write(
+                metadata.st_mode(),
+                metadata.st_uid(),
+                metadata.st_gid(),
+                uid,
+                &config.effective_write_gids,
+            ))
+        .then(|| format!("writable by uid {uid}"));
+    }
+
+    if let Some(parent_meta) = parent_meta
+        && !is_symlink
+        && metadata.is_file()
+        && breaks_setgid_inheritance(
+            parent_meta.st_mode(),
+            parent_meta.st_gid(),
+            metadata.st_gid(),
+        )
+    {
+        return Some(format!(
+            "group g{} differs from g{} of the setgid parent directory",
+            metadata.st_gid(),
+            parent_meta.st_gid()
+        ));
+    }
+
+    let permissions = PermissionBlock::from(metadata);
+
+    if config.other_not_exceeding_group && permissions.other_exceeds_group() {
+        return Some("\"other\" has permissions \"group\" doesn't have".to_string());
+    }
+
+    if let Some(reason) = report_filter_match(config, metadata, &permissions, is_symlink) {
+        return Some(reason);
+    }
+
+    if is_dir && config.directory_filter.is_none() {
+        return None;
+    }
+
+    if (metadata.is_file() || is_symlink) && config.file_filter.is_none() {
+        return None;
+    }
+
+    let filters = match if is_dir {
+        &config.directory_filter
+    } else {
+        &config.file_filter
+    } {
+        Some(value) => value,
+        None => &FilterSet {
+            filters: vec![Filter {
+                user_owner: Some(IdMatch::Exact(base_dir_meta.st_uid())),
+                group_owner: Some(IdMatch::Exact(base_dir_meta.st_gid())),
+                permissions: None,
+                primary_group: None,
+            }],
+        },
+    };
+
+    // The mode of a symlink is always rwxrwxrwx and never used, only its owner matters
+    let owners_only;
+    let filters = if is_symlink {
+        owners_only = filters.owners_only();
+        &owners_only
+    } else {
+        filters
+    };
+
+    let meta_uid = metadata.st_uid();
+    let meta_gid = metadata.st_gid();
+
+    let outcome = filters.matches(meta_uid, meta_gid, &permissions);
+    if outcome.is_match() {
+        return None;
+    }
+
+    // The outcome explains the mismatch of the closest filter
+    Some(format!(
+        "{permissions} u{meta_uid} g{meta_gid} matches none of the allowed {} filters: {} \
+         ({outcome})",
+        if is_dir {
+            "directory"
+        } else if is_symlink {
+            "file owner"
+        } else {
+            "file"
+        },
+        filters
+            .filters
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    ))
+}
+
+/// Describes the first `--report-filter` matching the object, symlinks are only matched by
+/// their owners.
+fn report_filter_match(
+    config: &ScanConfig,
+    metadata: &Metadata,
+    permissions: &PermissionBlock,
+    is_symlink: bool,
+) -> Option<String> {
+    let filters = config.report_filter.as_ref()?;
+    let (uid, gid) = (metadata.st_uid(), metadata.st_gid());
+
+    filters
+        .filters
+        .iter()
+        .filter_map(|filter| {
+            if !is_symlink {
+                Some(filter.clone())
+            } else if filter.user_owner.is_some() || filter.group_owner.is_some() {
+                Some(Filter {
+                    permissions: None,
+                    ..filter.clone()
+                })
+            } else {
+                // Nothing to check for a filter of only permissions
+                None
+            }
+        })
+        .find(|filter| filter.matches(uid, gid, permissions).is_match())
+        .map(|filter| format!("{permissions} u{uid} g{gid} matches the report filter {filter}"))
+}
+
+/// Counts a flagged object in the summaries.
+fn count_flagged(
+    path: &Path,
+    config: &ScanConfig,
+    metadata: &Metadata,
+    is_symlink: bool,
+    stats: &mut ScanStats,
+) {
+    stats.flagged += 1;
+
+    if config.worst_dirs.is_some() {
+        stats.count_flagged_in(path);
+    }
+
+    if config.age_buckets {
+        stats.count_flagged_age(metadata, unix_now());
+    }
+
+    if config.by_extension && !is_symlink && metadata.is_file() {
+        stats.count_flagged_extension(path);
+    }
+}
+
+#[allow(clippy::similar_names)]
+fn report_object(
+    path: &Path,
+    config: &ScanConfig,
+    metadata: &Metadata,
+    is_symlink: bool,
+    stats: &mut ScanStats,
+) -> anyhow::Result<()> {
+    count_flagged(path, config, metadata, is_symlink, stats);
+
+    let violation = || Violation {
+        via_symlink: stats.via_symlink.clone(),
+        ..Violation::new(path, metadata, is_symlink)
+    };
+
+    if let Some(sender) = &config.violation_sender {
+        sender
+            .send(Ok(violation()))
+            .map_err(|_| anyhow!("Stopping, the violations are no longer read"))?;
+
+        return Ok(());
+    }
+
+    if config.collect_violations {
+        let violation = violation();
+        stats.violations.push(violation);
+
+        return Ok(());
+    }
+
+    let record = || ViolationRecord {
+        via_symlink: stats
+            .via_symlink
+            .as_ref()
+            .map(|link| link.to_string_lossy().to_string()),
+        flat_permissions: config
+            .json_flatten_perms
+            .then(|| FlatPermissions::from_mode(metadata.st_mode())),
+        ..ViolationRecord::new(path, &config.base_dir, metadata, is_symlink)
+    };
+
+    if config.count {
+        return Ok(());
+    }
+
+    if config.format == OutputFormat::Json {
+        println!("{}", serde_json::to_string(&record())?);
+
+        return Ok(());
+    }
+
+    if let Some(template) = &config.template {
+        println!("{}", template.render(&record()));
+
+        return Ok(());
+    }
+
+    if config.print0 {
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(path.as_os_str().as_bytes())?;
+        stdout.write_all(b"\0")?;
+
+        return Ok(());
+    }
+
+    let permissions = config.mode_display.render(metadata.st_mode());
+    let meta_uid = metadata.st_uid();
+    let meta_gid = metadata.st_gid();
+    let kind = ObjectKind::from_metadata(metadata, is_symlink);
+
+    if config.csv {
+        println!(
+            "{}",
+            csv_record([
+                kind.name().to_string(),
+                permissions,
+                format!("{:04o}", metadata.st_mode() & 0o7777),
+                meta_uid.to_string(),
+                meta_gid.to_string(),
+                path.to_string_lossy().to_string(),
+            ])?
+        );
+
+        return Ok(());
+    }
+
+    let prefix = kind.prefix();
+    let permissions = if config.colorize {
+        config.mode_display.render_colored(metadata.st_mode())
+    } else {
+        permissions
+    };
+
+    let mut parts = vec![format!(
+        "{prefix}{permissions} {meta_uid: >5} {meta_gid: >5} {}",
+        config.quote_paths.quote(path)
+    )];
+
+    if config.git_blame
+        && !is_symlink
+        && metadata.is_file()
+        && let Some(author) = git_last_author(path)
+    {
+        parts.push(format!("(last author: {author})"));
+    }
+
+    if let Some(link) = &stats.via_symlink {
+        parts.push(format!("(via {})", config.quote_paths.quote(link)));
+    }
+
+    if config.annotate_symlink_target_type && is_symlink {
+        parts.push(format!("(-> {})", symlink_target_type(path)));
+    }
+
+    if config.dump_mode_bits {
+        parts.push(mode_bits(metadata.st_mode()));
+    }
+
+    println!("{}", parts.join(" "));
+
+    if config.resolve_chain && is_symlink {
+        for hop in symlink_chain(path) {
+            println!("    {hop}");
+        }
+    }
+
+    if config.show_parent_chain {
+        for line in parent_chain(config, path, &mut stats.ancestor_cache)? {
+            println!("    {line}");
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::{BTreeMap, BTreeSet, HashMap},
+        ffi::OsStr,
+        fs, io,
+        os::unix::{
+            ffi::OsStrExt,
+            fs::{MetadataExt, PermissionsExt, chown, lchown, symlink},
+            net::UnixListener,
+        },
+        panic::{self, AssertUnwindSafe},
+        path::{Path, PathBuf},
+        process::Command,
+        time::{Duration, Instant},
+    };
+
+    use clap::Parser;
+    use filetime::FileTime;
+
+    use crate::{
+        CheckResult, Outcome, Scanner, Violation,
+        accounts::Accounts,
+        check_single,
+        cli::Args,
+        config::ScanConfig,
+        effective_config, flush_trace, guard_panic, home_dir_findings,
+        input_parser::PermissionBlock,
+        open_trace,
+        output::{Decision, ObjectKind, TraceRecord},
+        parent_chain, path_dir_findings, read_dir_error, run, run_recursive,
+        stats::{PolicyComparison, ScanStats},
+        util::unix_now,
+    };
+
+    fn scan(args: &[&str], dir: &Path) -> (anyhow::Result<()>, ScanStats) {
+        let mut config = ScanConfig::from(&Args::parse_from(
+            ["permsearch", "-s"]
+                .iter()
+                .chain(args)
+                .chain([&dir.to_str().unwrap()]),
+        ));
+        open_trace(&mut config).unwrap();
+        let mut stats = ScanStats::default();
+        let result = run_recursive(
+            &config,
+            &config.base_dir,
+            &config.base_dir.metadata().unwrap(),
+            0,
+            &mut stats,
+        );
+        flush_trace(&config).unwrap();
+
+        (result, stats)
+    }
+
+    /// Reads the records of a `--trace-json` file.
+    fn trace_records(path: &Path) -> Vec<serde_json::Value> {
+        fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_profile_name() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, mode) in [("shared", 0o666), ("public", 0o644)] {
+            fs::write(dir.path().join(name), "").unwrap();
+            fs::set_permissions(dir.path().join(name), fs::Permissions::from_mode(mode)).unwrap();
+        }
+
+        let config = Args::parse_from([
+            "permsearch",
+            "--profile-name",
+            "web-root",
+            dir.path().to_str().unwrap(),
+        ]);
+
+        assert!(matches!(
+            check_single(&config, &dir.path().join("shared")).unwrap(),
+            CheckResult::Fail(_)
+        ));
+        assert_eq!(
+            check_single(&config, &dir.path().join("public")).unwrap(),
+            CheckResult::Pass
+        );
+    }
+
+    #[test]
+    fn test_safe_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        fs::create_dir(&root).unwrap();
+        // Mounting a filesystem and creating device nodes needs root
+        let succeeds = |command: &mut Command| command.status().is_ok_and(|s| s.success());
+        if !succeeds(
+            Command::new("mount")
+                .args(["-t", "tmpfs", "tmpfs"])
+                .arg(&root),
+        ) {
+            return;
+        }
+        fs::create_dir_all(root.join("dev")).unwrap();
+        fs::write(root.join("dev").join("file"), "").unwrap();
+        fs::create_dir(root.join("sub")).unwrap();
+        let mknod = |path: PathBuf| succeeds(Command::new("mknod").arg(path).args(["c", "1", "3"]));
+        let created = mknod(root.join("null")) && mknod(root.join("sub").join("null"));
+
+        let (result, stats) = scan(&["--safe-root", "-f", "u4000000000"], &root);
+        let (_, unsafe_stats) = scan(&["-f", "u4000000000"], &root);
+        assert!(succeeds(Command::new("umount").arg(&root)));
+        assert!(created);
+
+        // dev/ is skipped, the device nodes below the mount root are checked from their
+        // directory entries and the ones further down like any other object
+        result.unwrap();
+        assert_eq!(
+            (
+                stats.scanned_directories,
+                stats.scanned_files,
+                stats.scanned_other
+            ),
+            (2, 0, 2)
+        );
+        assert_eq!(stats.flagged, 2);
+        assert_eq!(
+            (unsafe_stats.scanned_directories, unsafe_stats.scanned_files),
+            (3, 1)
+        );
+    }
+
+    #[test]
+    fn test_scanned_by_type() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("a"), "").unwrap();
+        fs::write(dir.path().join("sub").join("b"), "").unwrap();
+        symlink(dir.path().join("a"), dir.path().join("sub").join("link")).unwrap();
+
+        let (result, stats) = scan(&[], dir.path());
+        result.unwrap();
+
+        assert_eq!(stats.scanned_files, 2);
+        assert_eq!(stats.scanned_directories, 2);
+        assert_eq!(stats.scanned_symlinks, 1);
+        assert_eq!(stats.scanned_other, 0);
+    }
+
+    #[test]
+    fn test_symlink_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("target"), "").unwrap();
+        symlink("target", dir.path().join("link")).unwrap();
+        symlink("missing", dir.path().join("dangling")).unwrap();
+        // Changing the owner to an arbitrary uid needs root
+        if chown(dir.path().join("target"), Some(0), None).is_err()
+            || lchown(dir.path().join("link"), Some(1000), None).is_err()
+            || lchown(dir.path().join("dangling"), Some(0), None).is_err()
+        {
+            return;
+        }
+
+        let (result, stats) = scan(&["-f", "rw-------u0"], dir.path());
+        result.unwrap();
+        assert_eq!(
+            (stats.scanned_symlinks, stats.access_errors, stats.flagged),
+            (2, 0, 2)
+        );
+
+        let config = Args::parse_from(["permsearch", "-f", "u0", dir.path().to_str().unwrap()]);
+        assert_eq!(
+            check_single(&config, &dir.path().join("link")).unwrap(),
+            CheckResult::Fail(
+                "rwxrwxrwx u1000 g0 matches none of the allowed file owner filters: u0 \
+                 (owner u1000, expected u0)"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            check_single(&config, &dir.path().join("dangling")).unwrap(),
+            CheckResult::Pass
+        );
+    }
+
+    #[test]
+    fn test_summary() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("a"), "").unwrap();
+        fs::write(dir.path().join("sub").join("b"), "").unwrap();
+        symlink("a", dir.path().join("link")).unwrap();
+        let _listener = UnixListener::bind(dir.path().join("socket")).unwrap();
+
+        let (result, stats) = scan(&["-f", "u4000000000"], dir.path());
+        result.unwrap();
+        assert_eq!(
+            stats.summary(),
+            "Examined 6 objects (2 files, 2 directories, 1 symlinks), 4 violations"
+        );
+    }
+
+    #[test]
+    fn test_report_filter() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, mode) in [("private", 0o600), ("shared", 0o666)] {
+            fs::write(dir.path().join(name), "").unwrap();
+            fs::set_permissions(dir.path().join(name), fs::Permissions::from_mode(mode)).unwrap();
+        }
+        symlink("shared", dir.path().join("link")).unwrap();
+        let uid = fs::metadata(dir.path()).unwrap().uid();
+
+        // Without an allowlist only the matching objects are reported
+        let (result, stats) = scan(&["--report-filter", "*******w*"], dir.path());
+        result.unwrap();
+        assert_eq!(stats.flagged, 1);
+        let config = Args::parse_from([
+            "permsearch",
+            "--report-filter",
+            "*******w*",
+            dir.path().to_str().unwrap(),
+        ]);
+        assert_eq!(
+            check_single(&config, &dir.path().join("shared")).unwrap(),
+            CheckResult::Fail(format!(
+                "rw-rw-rw- u{uid} g{} matches the report filter *******w*",
+                fs::metadata(dir.path()).unwrap().gid()
+            ))
+        );
+
+        let config = Args::parse_from([
+            "permsearch",
+            "--report-filter=-------w-",
+            "--wildcard-unspecified",
+            dir.path().to_str().unwrap(),
+        ]);
+        assert!(matches!(
+            check_single(&config, &dir.path().join("shared")).unwrap(),
+            CheckResult::Fail(_)
+        ));
+
+        // Objects allowed by the file filter are still reported if they match
+        let (result, stats) = scan(
+            &["-f", &format!("u{uid}"), "--report-filter", "*******w*"],
+            dir.path(),
+        );
+        result.unwrap();
+        assert_eq!(stats.flagged, 1);
+
+        // Symlinks are matched by their owners
+        let (result, stats) = scan(&["--report-filter", &format!("u{uid}")], dir.path());
+        result.unwrap();
+        assert_eq!(stats.flagged, 4);
+    }
+
+    #[test]
+    fn test_modification_time_bounds() {
+        let dir = tempfile::tempdir().unwrap();
+        let now = unix_now();
+        for (name, age) in [
+            ("fresh", 60),
+            ("week", 7 * 24 * 60 * 60),
+            ("old", 400 * 24 * 60 * 60),
+        ] {
+            let path = dir.path().join(name);
+            fs::write(&path, "").unwrap();
+            filetime::set_file_mtime(&path, FileTime::from_unix_time(now - age, 0)).unwrap();
+        }
+
+        let flagged = |args: &[&str]| {
+            let (result, stats) = scan(&[&["-f", "u4000000000"], args].concat(), dir.path());
+            result.unwrap();
+            stats.flagged
+        };
+        assert_eq!(flagged(&["--newer-than", "1d"]), 1);
+        assert_eq!(flagged(&["--older-than", "1d"]), 2);
+        assert_eq!(flagged(&["--newer-than", "30d", "--older-than", "1h"]), 1);
+        assert_eq!(flagged(&["--older-than", "1970-01-02"]), 0);
+        assert_eq!(flagged(&["--newer-than", &format!("@{}", now - 120)]), 1);
+    }
+
+    #[test]
+    fn test_sorted_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["m", "z", "a", "b"] {
+            fs::create_dir(dir.path().join(name)).unwrap();
+            for file in ["2", "10", "1"] {
+                fs::write(dir.path().join(name).join(file), "").unwrap();
+            }
+        }
+
+        let config = ScanConfig::from(&Args::parse_from([
+            "permsearch",
+            "-f",
+            "u4000000000",
+            dir.path().to_str().unwrap(),
+        ]));
+        let paths: Vec<PathBuf> = crate::scan(&config)
+            .unwrap()
+            .into_iter()
+            .map(|violation| violation.path)
+            .collect();
+
+        let mut sorted = paths.clone();
+        sorted.sort();
+        assert_eq!(paths.len(), 12);
+        assert_eq!(paths, sorted);
+        assert_eq!(paths[0], dir.path().join("a").join("1"));
+        assert_eq!(paths[1], dir.path().join("a").join("10"));
+    }
+
+    #[test]
+    fn test_sort_by_inode() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["a", "m", "b", "y"] {
+            fs::write(dir.path().join(name), "").unwrap();
+        }
+        fs::hard_link(dir.path().join("a"), dir.path().join("z")).unwrap();
+
+        let names = |args: &[&str]| {
+            let config = ScanConfig::from(&Args::parse_from(
+                ["permsearch", "-f", "u4000000000"]
+                    .iter()
+                    .chain(args)
+                    .chain([&dir.path().to_str().unwrap()]),
+            ));
+            crate::scan(&config)
+                .unwrap()
+                .into_iter()
+                .map(|violation| violation.path.file_name().unwrap().to_owned())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(names(&[]), ["a", "b", "m", "y", "z"]);
+        let by_inode = names(&["--sort-by", "inode"]);
+        let position = |name: &str| by_inode.iter().position(|other| other == name).unwrap();
+        assert_eq!(by_inode.len(), 5);
+        assert_eq!(position("a").abs_diff(position("z")), 1);
+    }
+
+    #[test]
+    fn test_sort_case_insensitive() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["banana", "Cherry", "Apple", "apple"] {
+            fs::write(dir.path().join(name), "").unwrap();
+        }
+
+        let names = |args: &[&str]| {
+            let config = ScanConfig::from(&Args::parse_from(
+                ["permsearch", "-f", "u4000000000"]
+                    .iter()
+                    .chain(args)
+                    .chain([&dir.path().to_str().unwrap()]),
+            ));
+            crate::scan(&config)
+                .unwrap()
+                .into_iter()
+                .map(|violation| violation.path.file_name().unwrap().to_owned())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(names(&[]), ["Apple", "Cherry", "apple", "banana"]);
+        assert_eq!(
+            names(&["--sort-case-insensitive"]),
+            ["Apple", "apple", "banana", "Cherry"]
+        );
+    }
+
+    #[test]
+    fn test_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["a", "b", "c"] {
+            fs::create_dir(dir.path().join(name)).unwrap();
+            for i in 0..5 {
+                fs::write(dir.path().join(name).join(i.to_string()), "").unwrap();
+            }
+        }
+
+        let (result, stats) = scan(&["-f", "u4000000000", "--limit", "7"], dir.path());
+        result.unwrap();
+        assert_eq!(stats.flagged, 7);
+        assert_eq!(stats.scanned_files, 7);
+
+        let (result, stats) = scan(&["-f", "u4000000000", "--limit", "100"], dir.path());
+        result.unwrap();
+        assert_eq!(stats.flagged, 15);
+
+        assert!(Args::try_parse_from(["permsearch", "--limit", "0", "/"]).is_err());
+    }
+
+    #[test]
+    fn test_object_types() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("sub").join("file"), "").unwrap();
+        let _listener = UnixListener::bind(dir.path().join("sub").join("socket")).unwrap();
+
+        let flagged = |types: &str| {
+            let (result, stats) = scan(&["-f", "u4000000000", "--type", types], dir.path());
+            result.unwrap();
+            stats.flagged
+        };
+        assert_eq!(flagged("socket"), 1);
+        assert_eq!(flagged("file,socket"), 2);
+        assert_eq!(flagged("block-device"), 0);
+    }
+
+    #[test]
+    fn test_size_bounds() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, size) in [("small", 10), ("medium", 2048), ("large", 20_000)] {
+            fs::write(dir.path().join(name), vec![0; size]).unwrap();
+        }
+
+        let (result, stats) = scan(&["-f", "u4000000000", "--min-size", "1k"], dir.path());
+        result.unwrap();
+        assert_eq!(stats.flagged, 2);
+
+        let (result, stats) = scan(
+            &["-f", "u4000000000", "--min-size", "1k", "--max-size", "10k"],
+            dir.path(),
+        );
+        result.unwrap();
+        assert_eq!(stats.flagged, 1);
+
+        let (result, stats) = scan(&["-f", "u4000000000", "--max-size", "10"], dir.path());
+        result.unwrap();
+        assert_eq!(stats.flagged, 1);
+    }
+
+    #[test]
+    fn test_scan_violations() {
+        let dir = tempfile::tempdir().unwrap();
+        let open = dir.path().join("open");
+        let private = dir.path().join("private");
+        fs::write(&open, "").unwrap();
+        fs::write(&private, "").unwrap();
+        fs::set_permissions(&open, fs::Permissions::from_mode(0o666)).unwrap();
+        fs::set_permissions(&private, fs::Permissions::from_mode(0o600)).unwrap();
+        let metadata = fs::metadata(&open).unwrap();
+
+        let config = ScanConfig::from(&Args::parse_from([
+            "permsearch",
+            "-f",
+            "rw-------",
+            dir.path().to_str().unwrap(),
+        ]));
+        assert_eq!(
+            crate::scan(&config).unwrap(),
+            [Violation {
+                path: open,
+                permissions: PermissionBlock::from_mode_bits(0o666),
+                uid: metadata.uid(),
+                gid: metadata.gid(),
+                kind: ObjectKind::File,
+                via_symlink: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_violations() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..3 {
+            fs::write(dir.path().join(i.to_string()), "").unwrap();
+        }
+        fs::write(dir.path().join(OsStr::from_bytes(b"\xff")), "").unwrap();
+
+        let config = ScanConfig::from(&Args::parse_from([
+            "permsearch",
+            "-f",
+            "u4000000000",
+            "--strict-utf8-paths",
+            dir.path().to_str().unwrap(),
+        ]));
+        let (found, errors): (Vec<_>, Vec<_>) = crate::violations(&config).partition(Result::is_ok);
+        assert_eq!(found.len(), 3);
+        assert_eq!(errors.len(), 1);
+        assert!(
+            errors[0]
+                .as_ref()
+                .unwrap_err()
+                .to_string()
+                .starts_with("non-UTF-8 file name")
+        );
+
+        // Dropping the iterator early stops the search
+        assert!(crate::violations(&config).next().is_some());
+
+        let config = ScanConfig::from(&Args::parse_from([
+            "permsearch",
+            dir.path().join("missing").to_str().unwrap(),
+        ]));
+        let items = crate::violations(&config).collect::<Vec<_>>();
+        assert_eq!(items.len(), 1);
+        assert!(items[0].is_err());
+    }
+
+    #[test]
+    fn test_violations_metadata_error() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a"), "").unwrap();
+        fs::create_dir_all(dir.path().join("b/inner")).unwrap();
+        fs::write(dir.path().join("c"), "").unwrap();
+        // Listable, but its children can't be examined
+        fs::set_permissions(dir.path().join("b"), fs::Permissions::from_mode(0o644)).unwrap();
+        if dir.path().join("b/inner").metadata().is_ok() {
+            // Running as root
+            fs::set_permissions(dir.path().join("b"), fs::Permissions::from_mode(0o755)).unwrap();
+            return;
+        }
+
+        let config = ScanConfig::from(&Args::parse_from([
+            "permsearch",
+            "-f",
+            "u4000000000",
+            dir.path().to_str().unwrap(),
+        ]));
+        let items = crate::violations(&config).collect::<Vec<_>>();
+        fs::set_permissions(dir.path().join("b"), fs::Permissions::from_mode(0o755)).unwrap();
+
+        let (found, errors): (Vec<_>, Vec<_>) = items.into_iter().partition(Result::is_ok);
+        assert_eq!(found.len(), 2);
+        assert_eq!(errors.len(), 1);
+        assert!(
+            errors[0]
+                .as_ref()
+                .unwrap_err()
+                .to_string()
+                .contains("b/inner")
+        );
+    }
+
+    #[test]
+    fn test_scanner() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("file"), "").unwrap();
+        fs::set_permissions(dir.path().join("file"), fs::Permissions::from_mode(0o666)).unwrap();
+        symlink("file", dir.path().join("link")).unwrap();
+
+        let scanner = Scanner::new(dir.path()).file_filter("u4000000000".parse().unwrap());
+        let paths = |violations: Vec<Violation>| {
+            violations
+                .into_iter()
+                .map(|violation| violation.path)
+                .collect::<BTreeSet<_>>()
+        };
+        assert_eq!(
+            paths(scanner.run().unwrap()),
+            BTreeSet::from([dir.path().join("file"), dir.path().join("link")])
+        );
+        assert_eq!(
+            paths(scanner.clone().ignore_symlinks(true).run().unwrap()),
+            BTreeSet::from([dir.path().join("file")])
+        );
+        assert_eq!(
+            scanner
+                .directory_filter("u4000000000".parse().unwrap())
+                .run()
+                .unwrap()
+                .len(),
+            3
+        );
+        assert!(Scanner::new(dir.path().join("missing")).run().is_err());
+
+        let config = ScanConfig {
+            file_filter: Some("u4000000000".parse().unwrap()),
+            max_depth: Some(0),
+            ..ScanConfig::new(dir.path())
+        };
+        assert!(Scanner::from(config).run().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_types_summary() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("a"), "").unwrap();
+        symlink(dir.path().join("a"), dir.path().join("link")).unwrap();
+        let _listener = UnixListener::bind(dir.path().join("sub").join("socket")).unwrap();
+
+        let (result, stats) = scan(&["--types-summary"], dir.path());
+        result.unwrap();
+
+        assert_eq!(
+            stats.types_summary(),
+            "Object types:\n      1 files\n      2 directories\n      1 symlinks\n      \
+             0 block devices\n      0 character devices\n      0 fifos\n      1 sockets"
+        );
+        assert_eq!(stats.scanned_other, 1);
+    }
+
+    #[test]
+    fn test_error_budget() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..5 {
+            fs::write(dir.path().join(OsStr::from_bytes(&[b'0' + i, 0xff])), "").unwrap();
+        }
+
+        let (result, stats) = scan(&["--strict-utf8-paths"], dir.path());
+        assert!(result.is_ok());
+        assert_eq!(stats.access_errors, 5);
+
+        let (result, stats) = scan(&["--strict-utf8-paths", "--error-budget", "2"], dir.path());
+        assert!(result.is_err());
+        assert_eq!(stats.access_errors, 3);
+    }
+
+    #[test]
+    fn test_defer_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..3 {
+            fs::write(dir.path().join(OsStr::from_bytes(&[b'0' + i, 0xff])), "").unwrap();
+        }
+
+        let (result, stats) = scan(&["--strict-utf8-paths"], dir.path());
+        assert!(result.is_ok());
+        assert!(stats.deferred_errors.is_empty());
+
+        let (result, stats) = scan(&["--strict-utf8-paths", "--defer-errors"], dir.path());
+        assert!(result.is_ok());
+        assert_eq!(stats.access_errors, 3);
+        assert_eq!(stats.deferred_errors.len(), 3);
+        assert!(
+            stats
+                .deferred_errors
+                .iter()
+                .all(|msg| msg.starts_with("non-UTF-8 file name"))
+        );
+    }
+
+    #[test]
+    fn test_read_dir_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let private = dir.path().join("private");
+        fs::create_dir(&private).unwrap();
+        fs::set_permissions(&private, fs::Permissions::from_mode(0o700)).unwrap();
+        let uid = fs::metadata(&private).unwrap().uid();
+        let gid = fs::metadata(&private).unwrap().gid();
+
+        // Root can read any directory, so the denied access is simulated
+        let denied = io::Error::from(io::ErrorKind::PermissionDenied);
+        assert_eq!(
+            read_dir_error(&private, &denied),
+            format!("accessing {private:?}: {denied} (owned by u{uid} g{gid})")
+        );
+
+        let missing = io::Error::from(io::ErrorKind::NotFound);
+        assert_eq!(
+            read_dir_error(&private, &missing),
+            format!("accessing {private:?}: {missing}")
+        );
+    }
+
+    #[test]
+    fn test_fail_fast_on_error() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..5 {
+            fs::create_dir(dir.path().join(i.to_string())).unwrap();
+            fs::write(
+                dir.path()
+                    .join(i.to_string())
+                    .join(OsStr::from_bytes(b"invalid\xff")),
+                "",
+            )
+            .unwrap();
+        }
+
+        let (result, stats) = scan(&["--strict-utf8-paths", "--fail-fast-on-error"], dir.path());
+        assert!(result.is_err());
+        assert_eq!(stats.access_errors, 1);
+        assert!(stats.scanned_directories < 6);
+    }
+
+    #[test]
+    fn test_worst_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, files) in [("a", 1), ("b", 3), ("c", 2), ("d", 2)] {
+            fs::create_dir(dir.path().join(name)).unwrap();
+            for i in 0..files {
+                fs::write(dir.path().join(name).join(i.to_string()), "").unwrap();
+            }
+        }
+
+        let (result, stats) = scan(&["-f", "u4000000000", "--worst-dirs", "3"], dir.path());
+        result.unwrap();
+
+        assert_eq!(
+            stats.worst_directories(3),
+            vec![
+                (dir.path().join("b").as_path(), 3),
+                (dir.path().join("c").as_path(), 2),
+                (dir.path().join("d").as_path(), 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_single() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("file");
+        fs::write(&file, "").unwrap();
+        fs::set_permissions(&file, fs::Permissions::from_mode(0o644)).unwrap();
+        let uid = fs::metadata(&file).unwrap().uid();
+        let gid = fs::metadata(&file).unwrap().gid();
+
+        let check = |args: &[&str]| {
+            let config = Args::parse_from(
+                ["permsearch", "--check-path", file.to_str().unwrap()]
+                    .iter()
+                    .chain(args)
+                    .chain([&dir.path().to_str().unwrap()]),
+            );
+            check_single(&config, &file).unwrap()
+        };
+
+        assert_eq!(check(&["-f", "rw-r--r--"]), CheckResult::Pass);
+        assert_eq!(check(&["-f", "rwxr-xr-x,rw-r--r--"]), CheckResult::Pass);
+        assert_eq!(
+            check(&["-f", "rwxr-xr-x,rw-------"]),
+            CheckResult::Fail(format!(
+                "rw-r--r-- u{uid} g{gid} matches none of the allowed file filters: \
+                 rwxr-xr-x, rw------- (u-x g-x o-x: user x unset, expected x; group x unset, \
+                 expected x; other x unset, expected x)"
+            ))
+        );
+
+        // Excluded objects pass like a search would skip them
+        assert_eq!(
+            check(&["-f", "rwxr-xr-x", "--min-size", "1M"]),
+            CheckResult::Excluded("size out of bounds")
+        );
+        assert_eq!(
+            check(&["-f", "rwxr-xr-x", "--type", "dir"]),
+            CheckResult::Excluded("other object type")
+        );
+
+        // The base directory only provides the default owners here
+        let config = Args::parse_from([
+            "permsearch",
+            "--check-path",
+            file.to_str().unwrap(),
+            "-f",
+            "rw-r--r--",
+        ]);
+        assert_eq!(config.base_dir, Path::new("/"));
+        assert_eq!(check_single(&config, &file).unwrap(), CheckResult::Pass);
+        assert!(Args::try_parse_from(["permsearch", "-f", "rw-r--r--"]).is_err());
+    }
+
+    #[test]
+    fn test_age_buckets() {
+        let dir = tempfile::tempdir().unwrap();
+        let now = unix_now();
+        let hour = 60 * 60;
+        for (name, age) in [
+            ("hour", hour),
+            ("days", 3 * 24 * hour),
+            ("weeks", 10 * 24 * hour),
+            ("months", 100 * 24 * hour),
+            ("years", 800 * 24 * hour),
+            ("years2", 900 * 24 * hour),
+        ] {
+            let path = dir.path().join(name);
+            fs::write(&path, "").unwrap();
+            filetime::set_file_mtime(&path, FileTime::from_unix_time(now - age, 0)).unwrap();
+        }
+
+        let (result, stats) = scan(&["-f", "u4000000000", "--age-buckets"], dir.path());
+        result.unwrap();
+
+        assert_eq!(stats.flagged_per_age, [1, 1, 1, 1, 2]);
+    }
+
+    #[test]
+    fn test_by_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("certs.d")).unwrap();
+        for name in [
+            "a.key",
+            "b.KEY",
+            "certs.d/c.Key",
+            "d.pem",
+            "README",
+            "e.tar.gz",
+        ] {
+            fs::write(dir.path().join(name), "").unwrap();
+        }
+        symlink(dir.path().join("a.key"), dir.path().join("link.key")).unwrap();
+
+        let (result, stats) = scan(&["-f", "u4000000000", "--by-extension"], dir.path());
+        result.unwrap();
+
+        assert_eq!(
+            stats.extension_distribution(),
+            vec![
+                (Some("key"), 3),
+                (None, 1),
+                (Some("gz"), 1),
+                (Some("pem"), 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("a/b/c")).unwrap();
+        fs::write(dir.path().join("top"), "").unwrap();
+        fs::write(dir.path().join("a/b/deep"), "").unwrap();
+
+        let (result, stats) = scan(&["--max-depth", "0"], dir.path());
+        result.unwrap();
+        assert_eq!((stats.scanned_directories, stats.scanned_files), (1, 0));
+
+        let (result, stats) = scan(&["--max-depth", "2"], dir.path());
+        result.unwrap();
+        assert_eq!((stats.scanned_directories, stats.scanned_files), (3, 1));
+
+        let (result, stats) = scan(&[], dir.path());
+        result.unwrap();
+        assert_eq!((stats.scanned_directories, stats.scanned_files), (4, 2));
+    }
+
+    #[test]
+    fn test_min_depth() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("a/b")).unwrap();
+        fs::write(dir.path().join("top"), "").unwrap();
+        fs::write(dir.path().join("a/b/deep"), "").unwrap();
+        symlink("top", dir.path().join("link")).unwrap();
+
+        let (result, stats) = scan(&["--min-depth", "2"], dir.path());
+        result.unwrap();
+        assert_eq!(
+            (
+                stats.scanned_directories,
+                stats.scanned_files,
+                stats.scanned_symlinks
+            ),
+            (1, 1, 0)
+        );
+
+        let (result, stats) = scan(&["--min-depth", "1", "--max-depth", "1"], dir.path());
+        result.unwrap();
+        assert_eq!(
+            (
+                stats.scanned_directories,
+                stats.scanned_files,
+                stats.scanned_symlinks
+            ),
+            (1, 1, 1)
+        );
+    }
+
+    #[test]
+    fn test_follow_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let tree = dir.path().join("tree");
+        let outside = dir.path().join("outside");
+        fs::create_dir(&tree).unwrap();
+        fs::create_dir(&outside).unwrap();
+        fs::write(outside.join("file"), "").unwrap();
+        symlink(&outside, tree.join("to_outside")).unwrap();
+        symlink(&tree, tree.join("to_self")).unwrap();
+        symlink(&outside, outside.join("loop")).unwrap();
+
+        let (result, stats) = scan(&[], &tree);
+        result.unwrap();
+        assert_eq!(
+            (
+                stats.scanned_directories,
+                stats.scanned_files,
+                stats.scanned_symlinks
+            ),
+            (1, 0, 2)
+        );
+
+        let (result, stats) = scan(&["--follow-symlinks"], &tree);
+        result.unwrap();
+        assert_eq!(
+            (
+                stats.scanned_directories,
+                stats.scanned_files,
+                stats.scanned_symlinks
+            ),
+            (2, 1, 0)
+        );
+    }
+
+    #[test]
+    fn test_follow_symlinks_to_sibling() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::set_permissions(dir.path().join("sub"), fs::Permissions::from_mode(0o777)).unwrap();
+        // Sorts before the directory it points to, which is then searched again
+        symlink("sub", dir.path().join("dlink")).unwrap();
+        // Sorts after it, so it's skipped
+        symlink("sub", dir.path().join("zlink")).unwrap();
+
+        let config = ScanConfig::from(&Args::parse_from([
+            "permsearch",
+            "-d",
+            "rwxr-xr-x",
+            "--follow-symlinks",
+            dir.path().to_str().unwrap(),
+        ]));
+        let mut paths = crate::scan(&config)
+            .unwrap()
+            .into_iter()
+            .map(|violation| violation.path)
+            .collect::<Vec<_>>();
+        paths.sort();
+        assert_eq!(paths, [dir.path().join("dlink"), dir.path().join("sub")]);
+    }
+
+    #[test]
+    fn test_report_under_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().canonicalize().unwrap();
+        let tree = dir.join("tree");
+        let target = dir.join("target");
+        fs::create_dir_all(target.join("sub")).unwrap();
+        fs::create_dir(&tree).unwrap();
+        fs::write(tree.join("file"), "").unwrap();
+        fs::write(target.join("sub").join("file"), "").unwrap();
+        symlink("../target", tree.join("link")).unwrap();
+
+        let findings = |args: &[&str]| {
+            let config = ScanConfig::from(&Args::parse_from(
+                ["permsearch", "-f", "u4000000000", "--follow-symlinks"]
+                    .iter()
+                    .chain(args)
+                    .chain([&tree.to_str().unwrap()]),
+            ));
+            crate::scan(&config)
+                .unwrap()
+                .into_iter()
+                .map(|violation| (violation.path, violation.via_symlink))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            findings(&[]),
+            [
+                (tree.join("file"), None),
+                (tree.join("link").join("sub").join("file"), None)
+            ]
+        );
+        assert_eq!(
+            findings(&["--report-under-target"]),
+            [
+                (tree.join("file"), None),
+                (target.join("sub").join("file"), Some(tree.join("link")))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_one_file_system() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("file"), "").unwrap();
+        if !Path::new("/proc/self").exists() {
+            return;
+        }
+        symlink("/proc", dir.path().join("proc")).unwrap();
+
+        let trace_file = tempfile::NamedTempFile::new().unwrap();
+
+        let (result, stats) = scan(
+            &[
+                "--follow-symlinks",
+                "--one-file-system",
+                "--trace-json",
+                trace_file.path().to_str().unwrap(),
+            ],
+            dir.path(),
+        );
+        result.unwrap();
+        assert_eq!((stats.scanned_directories, stats.scanned_files), (1, 1));
+        assert!(trace_records(trace_file.path()).iter().any(|record| {
+            record["decision"] == "excluded" && record["reason"] == "on another filesystem"
+        }));
+    }
+
+    #[test]
+    fn test_cross_into() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("file"), "").unwrap();
+        let succeeds = |command: &mut Command| command.status().is_ok_and(|s| s.success());
+        let mounts = [dir.path().join("allowed"), dir.path().join("other")];
+        for mount in &mounts {
+            fs::create_dir(mount).unwrap();
+            // Mounting a filesystem needs root
+            if !succeeds(
+                Command::new("mount")
+                    .args(["-t", "tmpfs", "tmpfs"])
+                    .arg(mount),
+            ) {
+                return;
+            }
+            fs::write(mount.join("file"), "").unwrap();
+        }
+
+        let trace_file = tempfile::NamedTempFile::new().unwrap();
+        let mut config = effective_config(&Args::parse_from([
+            "permsearch",
+            "-s",
+            "--one-file-system",
+            "--cross-into",
+            mounts[0].to_str().unwrap(),
+            "--trace-json",
+            trace_file.path().to_str().unwrap(),
+            dir.path().to_str().unwrap(),
+        ]))
+        .unwrap();
+        open_trace(&mut config).unwrap();
+        let mut stats = ScanStats::default();
+        let result = run_recursive(
+            &config,
+            &config.base_dir,
+            &config.base_dir.metadata().unwrap(),
+            0,
+            &mut stats,
+        );
+        flush_trace(&config).unwrap();
+        for mount in &mounts {
+            assert!(succeeds(Command::new("umount").arg(mount)));
+        }
+
+        result.unwrap();
+        assert_eq!((stats.scanned_directories, stats.scanned_files), (2, 2));
+        let excluded: Vec<_> = trace_records(trace_file.path())
+            .into_iter()
+            .filter(|record| record["decision"] == "excluded")
+            .map(|record| record["path"].clone())
+            .collect();
+        assert_eq!(excluded, [mounts[1].to_str().unwrap()]);
+    }
+
+    #[test]
+    fn test_permission_histogram() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        for name in ["a", "b", "sub/c"] {
+            fs::write(dir.path().join(name), "").unwrap();
+            fs::set_permissions(dir.path().join(name), fs::Permissions::from_mode(0o640)).unwrap();
+        }
+        fs::set_permissions(dir.path(), fs::Permissions::from_mode(0o750)).unwrap();
+        fs::set_permissions(dir.path().join("sub"), fs::Permissions::from_mode(0o750)).unwrap();
+
+        let (result, stats) = scan(&["--permission-histogram"], dir.path());
+        result.unwrap();
+        assert_eq!(
+            stats.mode_histogram,
+            BTreeMap::from([(0o640, 3), (0o750, 2)])
+        );
+        assert_eq!(
+            stats.mode_histogram_summary(),
+            "Permission histogram:\n   0640       3\n   0750       2"
+        );
+    }
+
+    #[test]
+    fn test_exclude() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("node_modules/pkg")).unwrap();
+        fs::create_dir_all(dir.path().join("app/node_modules")).unwrap();
+        fs::create_dir_all(dir.path().join("app/.git")).unwrap();
+        fs::write(dir.path().join("app/main.js"), "").unwrap();
+        fs::write(dir.path().join("app/node_modules/dep.js"), "").unwrap();
+        fs::write(dir.path().join("node_modules/pkg/index.js"), "").unwrap();
+
+        let scan_excluding = |globs: &[&str]| {
+            let mut args = vec!["permsearch", "-s"];
+            for glob in globs {
+                args.extend(["--exclude", glob]);
+            }
+            args.push(dir.path().to_str().unwrap());
+            let config = effective_config(&Args::parse_from(args)).unwrap();
+
+            let mut stats = ScanStats::default();
+            run_recursive(
+                &config,
+                &config.base_dir,
+                &config.base_dir.metadata().unwrap(),
+                0,
+                &mut stats,
+            )
+            .unwrap();
+            (stats.scanned_directories, stats.scanned_files)
+        };
+
+        assert_eq!(scan_excluding(&[]), (6, 3));
+        assert_eq!(scan_excluding(&["**/node_modules"]), (3, 1));
+        assert_eq!(scan_excluding(&["**/node_modules", "**/.git"]), (2, 1));
+        assert_eq!(scan_excluding(&["*.js"]), (6, 0));
+    }
+
+    #[test]
+    fn test_skip_empty_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("empty"), "").unwrap();
+        fs::write(dir.path().join("secret"), "hunter2").unwrap();
+        for name in ["empty", "secret"] {
+            fs::set_permissions(dir.path().join(name), fs::Permissions::from_mode(0o644)).unwrap();
+        }
+
+        let (result, stats) = scan(&["-f", "rw-------"], dir.path());
+        result.unwrap();
+        assert_eq!(stats.flagged, 2);
+
+        let (result, stats) = scan(&["-f", "rw-------", "--skip-empty-files"], dir.path());
+        result.unwrap();
+        assert_eq!((stats.scanned_files, stats.flagged), (2, 1));
+    }
+
+    #[test]
+    fn test_uid_bounds() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("root"), "").unwrap();
+        fs::write(dir.path().join("user"), "").unwrap();
+        // Changing the owner to an arbitrary uid needs root
+        if chown(dir.path().join("root"), Some(0), None).is_err()
+            || chown(dir.path().join("user"), Some(1000), None).is_err()
+        {
+            return;
+        }
+
+        let (result, stats) = scan(&["-f", "u4000000000"], dir.path());
+        result.unwrap();
+        assert_eq!(stats.flagged, 2);
+
+        let (result, stats) = scan(&["-f", "u4000000000", "--min-uid", "1000"], dir.path());
+        result.unwrap();
+        assert_eq!((stats.scanned_files, stats.flagged), (2, 1));
+
+        let (result, stats) = scan(&["-f", "u4000000000", "--max-uid", "999"], dir.path());
+        result.unwrap();
+        assert_eq!((stats.scanned_files, stats.flagged), (2, 1));
+    }
+
+    #[test]
+    fn test_long_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        // Deeper than PATH_MAX, built by moving one long chain into another
+        let chain: PathBuf = std::iter::repeat_n("d".repeat(200), 15).collect();
+        fs::create_dir_all(dir.path().join("outer").join(&chain)).unwrap();
+        fs::create_dir_all(dir.path().join("inner").join(&chain)).unwrap();
+        fs::rename(
+            dir.path().join("inner"),
+            dir.path().join("outer").join(&chain).join("inner"),
+        )
+        .unwrap();
+
+        let (result, stats) = scan(&[], dir.path());
+        result.unwrap();
+        assert_eq!(stats.access_errors, 1);
+
+        let (result, stats) = scan(&["--skip-long-paths"], dir.path());
+        result.unwrap();
+        assert_eq!(stats.access_errors, 0);
+        assert!(stats.scanned_directories > 1);
+    }
+
+    #[test]
+    fn test_strict_utf8_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(OsStr::from_bytes(b"invalid\xff")), "").unwrap();
+        fs::write(dir.path().join("valid"), "").unwrap();
+
+        let (result, stats) = scan(&["-f", "u4000000000"], dir.path());
+        result.unwrap();
+        assert_eq!((stats.flagged, stats.access_errors), (2, 0));
+
+        let (result, stats) = scan(&["-f", "u4000000000", "--strict-utf8-paths"], dir.path());
+        result.unwrap();
+        assert_eq!((stats.flagged, stats.access_errors), (1, 1));
+
+        let (result, _) = scan(&["--strict-utf8-paths", "--fail-fast-on-error"], dir.path());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parent_chain() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("a").join("b");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(nested.join("file"), "").unwrap();
+        for (path, mode) in [
+            (dir.path(), 0o755),
+            (&dir.path().join("a"), 0o750),
+            (&nested, 0o700),
+        ] {
+            fs::set_permissions(path, fs::Permissions::from_mode(mode)).unwrap();
+        }
+        let uid = fs::metadata(dir.path()).unwrap().uid();
+        let gid = fs::metadata(dir.path()).unwrap().gid();
+
+        let config = ScanConfig::from(&Args::parse_from([
+            "permsearch",
+            dir.path().to_str().unwrap(),
+        ]));
+        let mut cache = HashMap::new();
+        let chain = parent_chain(&config, &nested.join("file"), &mut cache).unwrap();
+
+        assert_eq!(
+            chain,
+            vec![
+                format!("drwxr-xr-x {uid: >5} {gid: >5} {}", dir.path().display()),
+                format!(
+                    "drwxr-x--- {uid: >5} {gid: >5} {}",
+                    dir.path().join("a").display()
+                ),
+                format!("drwx------ {uid: >5} {gid: >5} {}", nested.display()),
+            ]
+        );
+        assert_eq!(cache.len(), 3);
+
+        // Served from the cache
+        fs::set_permissions(&nested, fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(
+            parent_chain(&config, &nested.join("file"), &mut cache).unwrap(),
+            chain
+        );
+    }
+
+    #[test]
+    fn test_continue_on_panic() {
+        let check_all = |config: &ScanConfig, stats: &mut ScanStats| {
+            for name in ["a", "b", "c"] {
+                guard_panic(config, stats, Path::new(name), |stats| {
+                    assert_ne!(name, "b", "malformed object");
+                    stats.flagged += 1;
+                    Ok(())
+                })?;
+            }
+
+            anyhow::Ok(())
+        };
+
+        let config = ScanConfig {
+            continue_on_panic: true,
+            ..ScanConfig::new(".")
+        };
+        let mut stats = ScanStats::default();
+        check_all(&config, &mut stats).unwrap();
+        assert_eq!((stats.flagged, stats.access_errors), (2, 1));
+
+        let config = ScanConfig::new(".");
+        let mut stats = ScanStats::default();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| check_all(&config, &mut stats)));
+        assert!(result.is_err());
+        assert_eq!(stats.flagged, 1);
+    }
+
+    #[test]
+    fn test_profile() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..20 {
+            fs::create_dir(dir.path().join(i.to_string())).unwrap();
+            fs::write(dir.path().join(i.to_string()).join("file"), "").unwrap();
+        }
+
+        let started = Instant::now();
+        let (result, stats) = scan(&["-f", "u4000000000", "--profile"], dir.path());
+        let total = started.elapsed();
+        result.unwrap();
+
+        let profile = &stats.profile;
+        assert!(profile.read_dir > Duration::ZERO);
+        assert!(profile.metadata > Duration::ZERO);
+        assert!(profile.output > Duration::ZERO);
+        assert!(profile.read_dir + profile.metadata + profile.output <= total);
+
+        let summary = profile.summary(total);
+        assert!(summary.starts_with("Profile:\n  directory reads "));
+        for phase in ["metadata", "output", "other", "total"] {
+            assert!(summary.contains(&format!("\n  {phase} ")), "{summary}");
+        }
+    }
+
+    #[test]
+    fn test_created_newer_than() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("file"), "").unwrap();
+        let has_creation_time = fs::metadata(dir.path()).unwrap().created().is_ok();
+
+        let (result, stats) = scan(
+            &["-f", "u4000000000", "--created-newer-than", "1h"],
+            dir.path(),
+        );
+        result.unwrap();
+        assert_eq!(stats.flagged, 1);
+        assert_eq!(stats.creation_time_unsupported, !has_creation_time);
+
+        // Everything was created before the start of the search
+        let (result, stats) = scan(
+            &["-f", "u4000000000", "--created-newer-than", "0s"],
+            dir.path(),
+        );
+        result.unwrap();
+        assert_eq!(stats.flagged, u64::from(!has_creation_time));
+    }
+
+    #[test]
+    fn test_allow_missing_base() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing");
+        let missing = missing.to_str().unwrap();
+
+        assert!(run(&Args::parse_from(["permsearch", "-s", missing])).is_err());
+        assert!(
+            run(&Args::parse_from([
+                "permsearch",
+                "-s",
+                "--allow-missing-base",
+                missing
+            ]))
+            .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_setgid_inheritance_check() {
+        let dir = tempfile::tempdir().unwrap();
+        let shared = dir.path().join("shared");
+        fs::create_dir(&shared).unwrap();
+        fs::set_permissions(&shared, fs::Permissions::from_mode(0o2775)).unwrap();
+        fs::write(shared.join("inherited"), "").unwrap();
+        fs::write(shared.join("foreign"), "").unwrap();
+
+        let gid = fs::metadata(&shared).unwrap().gid();
+        // Changing the group to an arbitrary gid needs root
+        if chown(shared.join("foreign"), None, Some(gid + 1)).is_err() {
+            return;
+        }
+
+        let (result, stats) = scan(&["--setgid-inheritance-check"], dir.path());
+        result.unwrap();
+        assert_eq!(stats.flagged, 1);
+
+        let config = Args::parse_from([
+            "permsearch",
+            "--setgid-inheritance-check",
+            dir.path().to_str().unwrap(),
+        ]);
+        assert_eq!(
+            check_single(&config, &shared.join("foreign")).unwrap(),
+            CheckResult::Fail(format!(
+                "group g{} differs from g{gid} of the setgid parent directory",
+                gid + 1
+            ))
+        );
+        assert_eq!(
+            check_single(&config, &shared.join("inherited")).unwrap(),
+            CheckResult::Pass
+        );
+    }
+
+    #[test]
+    fn test_owner_inconsistency() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        for path in ["a", "b", "c", "sub/d"] {
+            fs::write(dir.path().join(path), "").unwrap();
+            fs::set_permissions(dir.path().join(path), fs::Permissions::from_mode(0o644)).unwrap();
+        }
+        fs::set_permissions(dir.path().join("c"), fs::Permissions::from_mode(0o600)).unwrap();
+
+        let uid = fs::metadata(dir.path().join("a")).unwrap().uid();
+        // Changing the owner to an arbitrary uid needs root
+        for path in ["b", "c"] {
+            if chown(dir.path().join(path), Some(uid + 1), None).is_err() {
+                return;
+            }
+        }
+
+        let (result, stats) = scan(&["--owner-inconsistency"], dir.path());
+        result.unwrap();
+        assert_eq!(
+            stats.owner_inconsistencies(),
+            vec![(dir.path(), 0o644, vec![uid, uid + 1])]
+        );
+    }
+
+    #[test]
+    fn test_print0() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("with space\nand newline"), "").unwrap();
+        fs::set_permissions(dir.path(), fs::Permissions::from_mode(0o777)).unwrap();
+
+        let (result, stats) = scan(&["--print0", "-f", "u4000000000"], dir.path());
+        result.unwrap();
+        assert_eq!(stats.flagged, 1);
+
+        assert!(Args::try_parse_from(["permsearch", "--print0", "."]).is_ok());
+        assert!(Args::try_parse_from(["permsearch", "--print0", "--format", "json", "."]).is_err());
+        assert!(Args::try_parse_from(["permsearch", "--print0", "--csv", "."]).is_err());
+    }
+
+    #[test]
+    fn test_home_dir_findings() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, mode) in [
+            ("alice", 0o700),
+            ("bob", 0o700),
+            ("carol", 0o755),
+            ("gone", 0o700),
+        ] {
+            fs::create_dir(dir.path().join(name)).unwrap();
+            fs::set_permissions(dir.path().join(name), fs::Permissions::from_mode(mode)).unwrap();
+        }
+        fs::write(dir.path().join("notes.txt"), "").unwrap();
+        // Changing the owner to an arbitrary uid needs root
+        if chown(dir.path().join("alice"), Some(1000), None).is_err()
+            || chown(dir.path().join("bob"), Some(1000), None).is_err()
+            || chown(dir.path().join("carol"), Some(1002), None).is_err()
+        {
+            return;
+        }
+
+        let accounts = Accounts::parse(
+            "alice:x:1000:1000::/home/alice:/bin/sh\n\
+             bob:x:1001:1001::/home/bob:/bin/sh\n\
+             carol:x:1002:1002::/home/carol:/bin/sh\n",
+            "",
+        );
+        assert_eq!(
+            home_dir_findings(dir.path(), &accounts).unwrap(),
+            vec![
+                (
+                    dir.path().join("bob"),
+                    "owned by u1000, but bob is u1001".to_string()
+                ),
+                (
+                    dir.path().join("carol"),
+                    "accessible by group or others".to_string()
+                ),
+                (dir.path().join("gone"), "no user called gone".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_path_dir_findings() {
+        let dir = tempfile::tempdir().unwrap();
+        let secure = dir.path().join("bin");
+        let insecure = dir.path().join("tmp-bin");
+        fs::create_dir(&secure).unwrap();
+        fs::create_dir(&insecure).unwrap();
+        fs::set_permissions(&insecure, fs::Permissions::from_mode(0o1777)).unwrap();
+        fs::write(secure.join("tool"), "").unwrap();
+        fs::write(secure.join("writable"), "").unwrap();
+        fs::set_permissions(secure.join("tool"), fs::Permissions::from_mode(0o755)).unwrap();
+        fs::set_permissions(secure.join("writable"), fs::Permissions::from_mode(0o777)).unwrap();
+        // The other checks assume root owns everything
+        if chown(dir.path(), Some(0), Some(0)).is_err()
+            || chown(&secure, Some(0), Some(0)).is_err()
+            || chown(&insecure, Some(0), Some(0)).is_err()
+            || chown(secure.join("tool"), Some(1000), None).is_err()
+            || chown(secure.join("writable"), Some(0), None).is_err()
+        {
+            return;
+        }
+
+        let list = format!(
+            "{}::{}:{}",
+            secure.to_str().unwrap(),
+            insecure.to_str().unwrap(),
+            dir.path().join("missing").to_str().unwrap()
+        );
+        assert_eq!(
+            path_dir_findings(OsStr::new(&list)),
+            vec![
+                (secure.join("tool"), "owned by u1000".to_string()),
+                (secure.join("writable"), "world-writable".to_string()),
+                (insecure, "world-writable".to_string()),
+            ]
+        );
+
+        assert!(Args::try_parse_from(["permsearch", "--check-path-dirs"]).is_ok());
+    }
+
+    #[test]
+    fn test_compare_policy() {
+        let dir = tempfile::tempdir().unwrap();
+        let tree = dir.path().join("tree");
+        fs::create_dir(&tree).unwrap();
+        for (name, mode) in [("a", 0o644), ("b", 0o600), ("c", 0o640), ("d", 0o755)] {
+            fs::write(tree.join(name), "").unwrap();
+            fs::set_permissions(tree.join(name), fs::Permissions::from_mode(mode)).unwrap();
+        }
+        let policy = dir.path().join("policy");
+        fs::write(&policy, "file = rw-------,rw-r-----\n").unwrap();
+
+        let config = effective_config(&Args::parse_from([
+            "permsearch",
+            "-s",
+            "-f",
+            "rw-r--r--",
+            "--compare-policy",
+            policy.to_str().unwrap(),
+            tree.to_str().unwrap(),
+        ]))
+        .unwrap();
+        let mut stats = ScanStats::default();
+        run_recursive(
+            &config,
+            &config.base_dir,
+            &config.base_dir.metadata().unwrap(),
+            0,
+            &mut stats,
+        )
+        .unwrap();
+
+        assert_eq!(stats.flagged, 3);
+        assert_eq!(
+            stats.policy_comparison,
+            PolicyComparison {
+                both: 1,
+                only_current: 2,
+                only_other: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_outcome() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("file"), "").unwrap();
+        let outcome = |args: &[&str]| {
+            run(&Args::parse_from(
+                ["permsearch", "-s"]
+                    .iter()
+                    .chain(args)
+                    .chain([&dir.path().to_str().unwrap()]),
+            ))
+            .unwrap()
+        };
+
+        assert_eq!(outcome(&["-f", "u4000000000"]), Outcome::flagged(1));
+        assert_eq!(outcome(&["-d", "u4000000000"]), Outcome::flagged(1));
+        assert_eq!(outcome(&["-f", "*********"]), Outcome::default());
+        assert_eq!(
+            outcome(&["--count", "-f", "u4000000000"]),
+            Outcome::flagged(1)
+        );
+
+        let mut stats = ScanStats {
+            flagged: 3,
+            ..ScanStats::default()
+        };
+        assert_eq!(Outcome::from_stats(&stats).exit_code(), 1);
+        stats.access_errors = 1;
+        assert_eq!(Outcome::from_stats(&stats).exit_code(), 2);
+        assert_eq!(Outcome::from_stats(&ScanStats::default()).exit_code(), 0);
+    }
+
+    #[test]
+    fn test_trace_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let tree = dir.path().join("tree");
+        fs::create_dir(&tree).unwrap();
+        fs::write(tree.join("empty"), "").unwrap();
+        fs::write(tree.join("secret"), "hunter2").unwrap();
+        symlink("secret", tree.join("link")).unwrap();
+        let trace_file = dir.path().join("trace.json");
+        let trace_arg = trace_file.to_str().unwrap();
+
+        let (result, _) = scan(
+            &[
+                "-i",
+                "--skip-empty-files",
+                "-f",
+                "u4000000000",
+                "--trace-json",
+                trace_arg,
+            ],
+            &tree,
+        );
+        result.unwrap();
+
+        let record = |decision, path: &Path, reason: Option<&str>| {
+            serde_json::to_value(TraceRecord {
+                decision,
+                path: Some(path.to_string_lossy().to_string()),
+                reason: reason.map(ToString::to_string),
+            })
+            .unwrap()
+        };
+        let trace = trace_records(&trace_file);
+        assert_eq!(trace[0], record(Decision::Enter, &tree, None));
+        assert!(trace.contains(&record(Decision::SkipSymlink, &tree.join("link"), None)));
+        assert!(trace.contains(&record(
+            Decision::Excluded,
+            &tree.join("empty"),
+            Some("empty file")
+        )));
+        assert!(trace.iter().any(|record| {
+            record["decision"] == "flagged"
+                && record["path"] == tree.join("secret").to_str().unwrap()
+        }));
+
+        let invalid = tree.join(OsStr::from_bytes(b"invalid\xff"));
+        fs::write(&invalid, "").unwrap();
+        let (result, _) = scan(&["--strict-utf8-paths", "--trace-json", trace_arg], &tree);
+        result.unwrap();
+        let trace = trace_records(&trace_file);
+        let error = trace
+            .iter()
+            .find(|record| record["decision"] == "error")
+            .unwrap();
+        assert_eq!(error["path"], invalid.to_string_lossy().as_ref());
+        fs::remove_file(&invalid).unwrap();
+
+        run(&Args::parse_from([
+            "permsearch",
+            "-s",
+            "-f",
+            "u4000000000",
+            "--trace-json",
+            trace_arg,
+            tree.to_str().unwrap(),
+        ]))
+        .unwrap();
+        let trace = fs::read_to_string(&trace_file).unwrap();
+        assert_eq!(trace.lines().count(), 4);
+        assert!(trace.starts_with("{\"decision\":\"enter\",\"path\":"));
+    }
 }
@@ -1,3 +1,5 @@
+use std::process::exit;
+
 use clap::Parser;
 use permsearch::{cli::Args, run, util::exit_with_error};
 
@@ -5,12 +7,9 @@ use permsearch::{cli::Args, run, util::exit_with_error};
 fn main() {
     let args = Args::parse();
 
-    if !args.base_dir.exists() {
-        exit_with_error(&format!("Base directory {:?} doesn't exist", args.base_dir));
-    }
-
-    if let Err(err) = run(&args) {
-        eprintln!("{err}");
+    match run(&args) {
+        Ok(outcome) => exit(outcome.exit_code()),
+        Err(err) => exit_with_error(&err.to_string()),
     }
 }
 
@@ -0,0 +1,984 @@
+use std::{
+    fs::Metadata,
+    io::{self, IsTerminal},
+    os::{
+        linux::fs::MetadataExt,
+        unix::{ffi::OsStrExt, fs::FileTypeExt},
+    },
+    path::Path,
+};
+
+use anyhow::bail;
+use clap::{
+    ValueEnum,
+    builder::styling::{AnsiColor, Color, Style},
+};
+use serde::Serialize;
+
+use crate::{
+    input_parser::{Filter, FilterSet, IdMatch, PartialPermissionBlock, PermissionState},
+    special_bits::{OTHER_WRITE, SETGID, SETUID, STICKY},
+    util::no_color,
+};
+
+/// Kind of a filesystem object as shown in the output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ObjectKind {
+    File,
+    #[serde(rename = "dir")]
+    #[value(name = "dir")]
+    Directory,
+    Symlink,
+    Fifo,
+    Socket,
+    CharDevice,
+    BlockDevice,
+}
+
+impl ObjectKind {
+    #[must_use]
+    pub fn from_metadata(metadata: &Metadata, is_symlink: bool) -> Self {
+        let file_type = metadata.file_type();
+
+        if is_symlink {
+            ObjectKind::Symlink
+        } else if file_type.is_dir() {
+            ObjectKind::Directory
+        } else if file_type.is_fifo() {
+            ObjectKind::Fifo
+        } else if file_type.is_socket() {
+            ObjectKind::Socket
+        } else if file_type.is_char_device() {
+            ObjectKind::CharDevice
+        } else if file_type.is_block_device() {
+            ObjectKind::BlockDevice
+        } else {
+            ObjectKind::File
+        }
+    }
+
+    /// Type character in front of the symbolic permissions, like in `ls -l`.
+    #[must_use]
+    pub fn prefix(self) -> char {
+        match self {
+            ObjectKind::File => '-',
+            ObjectKind::Directory => 'd',
+            ObjectKind::Symlink => 'l',
+            ObjectKind::Fifo => 'p',
+            ObjectKind::Socket => 's',
+            ObjectKind::CharDevice => 'c',
+            ObjectKind::BlockDevice => 'b',
+        }
+    }
+
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            ObjectKind::File => "file",
+            ObjectKind::Directory => "dir",
+            ObjectKind::Symlink => "symlink",
+            ObjectKind::Fifo => "fifo",
+            ObjectKind::Socket => "socket",
+            ObjectKind::CharDevice => "char-device",
+            ObjectKind::BlockDevice => "block-device",
+        }
+    }
+}
+
+/// Format of the findings on stdout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum OutputFormat {
+    /// One line per finding, like `ls -l`
+    #[default]
+    Human,
+    /// One JSON object per finding and line
+    Json,
+}
+
+/// Finding as written by `--format json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ViolationRecord {
+    pub path: String,
+    /// Path relative to the base directory, unless the finding is outside of it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub relpath: Option<String>,
+    #[serde(rename = "type")]
+    pub kind: ObjectKind,
+    pub uid: u32,
+    pub gid: u32,
+    pub mode_octal: String,
+    pub mode_symbolic: String,
+    /// Symlink the finding was reached through with `--report-under-target`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub via_symlink: Option<String>,
+    /// Set with `--json-flatten-perms`
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub flat_permissions: Option<FlatPermissions>,
+}
+
+impl ViolationRecord {
+    #[must_use]
+    pub fn new(path: &Path, base_dir: &Path, metadata: &Metadata, is_symlink: bool) -> Self {
+        Self {
+            path: path.to_string_lossy().to_string(),
+            relpath: path.strip_prefix(base_dir).ok().map(|relpath| {
+                if relpath.as_os_str().is_empty() {
+                    ".".to_string()
+                } else {
+                    relpath.to_string_lossy().to_string()
+                }
+            }),
+            kind: ObjectKind::from_metadata(metadata, is_symlink),
+            uid: metadata.st_uid(),
+            gid: metadata.st_gid(),
+            mode_octal: ModeDisplay::Octal.render(metadata.st_mode()),
+            mode_symbolic: ModeDisplay::SymbolicSpecial.render(metadata.st_mode()),
+            via_symlink: None,
+            flat_permissions: None,
+        }
+    }
+}
+
+/// Permission bits of a finding as separate fields, as written by `--json-flatten-perms`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct FlatPermissions {
+    pub user_read: bool,
+    pub user_write: bool,
+    pub user_execute: bool,
+    pub group_read: bool,
+    pub group_write: bool,
+    pub group_execute: bool,
+    pub other_read: bool,
+    pub other_write: bool,
+    pub other_execute: bool,
+    pub setuid: bool,
+    pub setgid: bool,
+    pub sticky: bool,
+}
+
+impl FlatPermissions {
+    #[must_use]
+    pub fn from_mode(mode: u32) -> Self {
+        let is_set = |bit: u32| mode & bit != 0;
+
+        Self {
+            user_read: is_set(0o400),
+            user_write: is_set(0o200),
+            user_execute: is_set(0o100),
+            group_read: is_set(0o040),
+            group_write: is_set(0o020),
+            group_execute: is_set(0o010),
+            other_read: is_set(0o004),
+            other_write: is_set(0o002),
+            other_execute: is_set(0o001),
+            setuid: is_set(SETUID),
+            setgid: is_set(SETGID),
+            sticky: is_set(STICKY),
+        }
+    }
+}
+
+/// Kind of traversal decision written by `--trace-json`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Decision {
+    Enter,
+    SkipSymlink,
+    Excluded,
+    Flagged,
+    Error,
+}
+
+/// Traversal decision as written by `--trace-json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TraceRecord {
+    pub decision: Decision,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// Output line of a finding loaded with `--template-file`.
+///
+/// Placeholders are `%p` (path), `%t` (type), `%M` (symbolic mode), `%m` (octal mode),
+/// `%U` (uid), `%G` (gid) and `%%` for a literal `%`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    parts: Vec<TemplatePart>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TemplatePart {
+    Text(String),
+    Placeholder(char),
+}
+
+impl Template {
+    /// Parses a template, dropping a single trailing newline.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the template contains an unknown placeholder.
+    pub fn parse(template: &str) -> anyhow::Result<Self> {
+        let template = template.strip_suffix('\n').unwrap_or(template);
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars();
+
+        while let Some(char) = chars.next() {
+            if char != '%' {
+                text.push(char);
+                continue;
+            }
+
+            match chars.next() {
+                Some('%') => text.push('%'),
+                Some(placeholder @ ('p' | 't' | 'M' | 'm' | 'U' | 'G')) => {
+                    if !text.is_empty() {
+                        parts.push(TemplatePart::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(TemplatePart::Placeholder(placeholder));
+                }
+                Some(other) => bail!("Unknown placeholder \"%{other}\" in template"),
+                None => bail!("Incomplete placeholder at the end of the template"),
+            }
+        }
+
+        if !text.is_empty() {
+            parts.push(TemplatePart::Text(text));
+        }
+
+        Ok(Self { parts })
+    }
+
+    #[must_use]
+    pub fn render(&self, violation: &ViolationRecord) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                TemplatePart::Text(text) => text.clone(),
+                TemplatePart::Placeholder('p') => violation.path.clone(),
+                TemplatePart::Placeholder('t') => violation.kind.name().to_string(),
+                TemplatePart::Placeholder('M') => violation.mode_symbolic.clone(),
+                TemplatePart::Placeholder('m') => violation.mode_octal.clone(),
+                TemplatePart::Placeholder('U') => violation.uid.to_string(),
+                TemplatePart::Placeholder(_) => violation.gid.to_string(),
+            })
+            .collect()
+    }
+}
+
+/// How the permissions of a finding are shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum ModeDisplay {
+    /// Plain `rwx`, ignoring the special bits
+    #[default]
+    Symbolic,
+    /// `rwx` with `s`/`S` and `t`/`T` for the special bits, like `ls -l`
+    SymbolicSpecial,
+    /// Octal digits including the special bits, like `4755`
+    Octal,
+}
+
+impl ModeDisplay {
+    #[must_use]
+    pub fn render(self, mode: u32) -> String {
+        if self == ModeDisplay::Octal {
+            return format!("{:04o}", mode & 0o7777);
+        }
+
+        let mut rendered: Vec<char> = "rwxrwxrwx"
+            .chars()
+            .enumerate()
+            .map(|(index, char)| {
+                if mode & (0o400 >> index) == 0 {
+                    '-'
+                } else {
+                    char
+                }
+            })
+            .collect();
+
+        if self == ModeDisplay::SymbolicSpecial {
+            for (bit, index, char) in [(SETUID, 2, 's'), (SETGID, 5, 's'), (STICKY, 8, 't')] {
+                if mode & bit != 0 {
+                    rendered[index] = if rendered[index] == '-' {
+                        char.to_ascii_uppercase()
+                    } else {
+                        char
+                    };
+                }
+            }
+        }
+
+        rendered.into_iter().collect()
+    }
+
+    /// Renders `mode` like [`ModeDisplay::render`], with the write bit of "other" in red and
+    /// the execute bits in green. Octal modes are red as a whole if they're writable by
+    /// "other" and green if they're executable.
+    #[must_use]
+    pub fn render_colored(self, mode: u32) -> String {
+        let red = Style::new()
+            .bold()
+            .fg_color(Some(Color::Ansi(AnsiColor::Red)));
+        let green = Style::new()
+            .bold()
+            .fg_color(Some(Color::Ansi(AnsiColor::Green)));
+        let rendered = self.render(mode);
+
+        if self == ModeDisplay::Octal {
+            return if mode & OTHER_WRITE != 0 {
+                format!("{red}{rendered}{red:#}")
+            } else if mode & 0o111 != 0 {
+                format!("{green}{rendered}{green:#}")
+            } else {
+                rendered
+            };
+        }
+
+        rendered
+            .chars()
+            .enumerate()
+            .map(|(index, char)| match char {
+                'w' if index == 7 => format!("{red}{char}{red:#}"),
+                'x' | 's' | 't' => format!("{green}{char}{green:#}"),
+                _ => char.to_string(),
+            })
+            .collect()
+    }
+}
+
+/// When the output is colored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum ColorChoice {
+    /// If stdout is a terminal and `NO_COLOR` isn't set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    #[must_use]
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Auto => io::stdout().is_terminal() && !no_color(),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+/// Order in which the entries of a directory are visited, and their findings printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum SortOrder {
+    /// By file name
+    #[default]
+    Name,
+    /// By device and inode number, which keeps hard links together
+    Inode,
+}
+
+/// How paths are quoted in the output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum QuotePaths {
+    /// Printed as they are
+    #[default]
+    None,
+    /// Single-quoted for POSIX shells if they contain special characters
+    Shell,
+    /// Like shell, but control characters and invalid UTF-8 are escaped in `$'...'`
+    C,
+}
+
+impl QuotePaths {
+    #[must_use]
+    pub fn quote(self, path: &Path) -> String {
+        let bytes = path.as_os_str().as_bytes();
+        let is_safe = |byte: &u8| byte.is_ascii_alphanumeric() || b"_-./,:@%+=".contains(byte);
+
+        match self {
+            QuotePaths::None => path.to_string_lossy().to_string(),
+            QuotePaths::C
+                if std::str::from_utf8(bytes).is_err()
+                    || bytes.iter().any(u8::is_ascii_control) =>
+            {
+                c_quote(bytes)
+            }
+            QuotePaths::Shell | QuotePaths::C => {
+                if !bytes.is_empty() && bytes.iter().all(is_safe) {
+                    path.to_string_lossy().to_string()
+                } else {
+                    format!("'{}'", path.to_string_lossy().replace('\'', r"'\''"))
+                }
+            }
+        }
+    }
+}
+
+/// Quotes `bytes` as `$'...'`, escaping control characters and bytes that aren't valid
+/// UTF-8.
+fn c_quote(bytes: &[u8]) -> String {
+    let escaped: String = bytes
+        .utf8_chunks()
+        .flat_map(|chunk| {
+            chunk
+                .valid()
+                .chars()
+                .map(|char| match char {
+                    '\t' => r"\t".to_string(),
+                    '\n' => r"\n".to_string(),
+                    '\r' => r"\r".to_string(),
+                    '\\' => r"\\".to_string(),
+                    '\'' => r"\'".to_string(),
+                    char if char.is_ascii_control() => format!(r"\x{:02x}", u32::from(char)),
+                    char => char.to_string(),
+                })
+                .chain(chunk.invalid().iter().map(|byte| format!(r"\x{byte:02x}")))
+                .collect::<Vec<_>>()
+        })
+        .collect();
+
+    format!("$'{escaped}'")
+}
+
+/// Imports needed by the expressions of [`rust_snippet`].
+pub const RUST_SNIPPET_IMPORTS: &str = "use permsearch::input_parser::{\n    \
+    Filter, FilterSet, IdMatch, PartialPermissionBlock, PermissionBlock, PermissionState,\n};";
+
+/// Formats `filters` as a Rust expression building the same [`FilterSet`] with this crate.
+#[must_use]
+pub fn rust_snippet(filters: &FilterSet) -> String {
+    let block = |name: &str, block: &PartialPermissionBlock| {
+        format!(
+            "                {name}: PartialPermissionBlock {{\n                    \
+             read: PermissionState::{:?},\n                    \
+             write: PermissionState::{:?},\n                    \
+             execute: PermissionState::{:?},\n                }},",
+            block.read, block.write, block.execute
+        )
+    };
+
+    let mut lines = vec!["FilterSet {".to_string(), "    filters: vec![".to_string()];
+
+    for filter in &filters.filters {
+        lines.push("        Filter {".to_string());
+        lines.push(format!(
+            "            user_owner: {},",
+            id_snippet(filter.user_owner.as_ref())
+        ));
+        lines.push(format!(
+            "            group_owner: {},",
+            id_snippet(filter.group_owner.as_ref())
+        ));
+
+        match &filter.permissions {
+            Some(permissions) => {
+                lines.push("            permissions: Some(PermissionBlock {".to_string());
+                lines.push(block("user", &permissions.user));
+                lines.push(block("group", &permissions.group));
+                lines.push(block("other", &permissions.other));
+                lines.push(block("special", &permissions.special));
+                lines.push("            }),".to_string());
+            }
+            None => lines.push("            permissions: None,".to_string()),
+        }
+        lines.push(format!(
+            "            primary_group: {:?},",
+            filter.primary_group
+        ));
+
+        lines.push("        },".to_string());
+    }
+
+    lines.push("    ],".to_string());
+    lines.push("}".to_string());
+
+    lines.join("\n")
+}
+
+/// Formats an owner of a filter as a Rust expression.
+fn id_snippet(id: Option<&IdMatch>) -> String {
+    match id {
+        None => "None".to_string(),
+        Some(IdMatch::AnyOf(ids)) => format!("Some(IdMatch::AnyOf(vec!{ids:?}))"),
+        Some(id) => format!("Some(IdMatch::{id:?})"),
+    }
+}
+
+/// Formats a `find` command listing the objects below `base` that match none of the
+/// directory or file filters.
+///
+/// Types without a filter aren't listed. `find_options` (like `-xdev`) are put in front of
+/// the expression.
+#[must_use]
+pub fn find_command(
+    base: &Path,
+    find_options: &[String],
+    directory_filter: Option<&FilterSet>,
+    file_filter: Option<&FilterSet>,
+) -> String {
+    let types: Vec<String> = [("d", directory_filter), ("f", file_filter)]
+        .into_iter()
+        .filter_map(|(kind, filters)| {
+            let alternatives: Vec<String> = filters?.filters.iter().map(find_predicates).collect();
+            Some(format!(
+                "-type {kind} ! \\( {} \\)",
+                alternatives.join(" -o ")
+            ))
+        })
+        .collect();
+
+    let expression = if types.len() > 1 {
+        types
+            .iter()
+            .map(|predicates| format!("\\( {predicates} \\)"))
+            .collect::<Vec<_>>()
+            .join(" -o ")
+    } else {
+        types.concat()
+    };
+
+    [
+        "find".to_string(),
+        QuotePaths::Shell.quote(base),
+        find_options.join(" "),
+        expression,
+    ]
+    .into_iter()
+    .filter(|part| !part.is_empty())
+    .collect::<Vec<_>>()
+    .join(" ")
+}
+
+/// Formats the `find` predicates matching an owner id, using `-uid +N` and `-uid -N` for the
+/// bounds of a range.
+fn id_predicates(test: &str, id: &IdMatch) -> Vec<String> {
+    match *id {
+        IdMatch::Exact(id) => vec![format!("{test} {id}")],
+        IdMatch::AnyOf(ref ids) => {
+            let alternatives: Vec<String> = ids.iter().map(|id| format!("{test} {id}")).collect();
+            vec![format!("\\( {} \\)", alternatives.join(" -o "))]
+        }
+        IdMatch::Range(low, high) => [
+            low.checked_sub(1).map(|below| format!("{test} +{below}")),
+            high.checked_add(1).map(|above| format!("{test} -{above}")),
+        ]
+        .into_iter()
+        .flatten()
+        .collect(),
+    }
+}
+
+/// Formats the `find` predicates matching the objects allowed by `filter`.
+fn find_predicates(filter: &Filter) -> String {
+    let mut predicates = Vec::new();
+
+    if let Some(uid) = &filter.user_owner {
+        predicates.extend(id_predicates("-uid", uid));
+    }
+    if let Some(gid) = &filter.group_owner {
+        predicates.extend(id_predicates("-gid", gid));
+    }
+
+    if let Some(permissions) = &filter.permissions {
+        let mut set = 0;
+        let mut unset = 0;
+
+        for (block, shift) in [
+            (&permissions.special, 9),
+            (&permissions.user, 6),
+            (&permissions.group, 3),
+            (&permissions.other, 0),
+        ] {
+            for (state, bit) in [(block.read, 4), (block.write, 2), (block.execute, 1)] {
+                match state {
+                    PermissionState::SET => set |= bit << shift,
+                    PermissionState::UNSET => unset |= bit << shift,
+                    PermissionState::WILDCARD => {}
+                }
+            }
+        }
+
+        if set != 0 {
+            predicates.push(format!("-perm -{set:04o}"));
+        }
+        if unset != 0 {
+            predicates.push(format!("! -perm /{unset:04o}"));
+        }
+    }
+
+    if predicates.is_empty() {
+        "-true".to_string()
+    } else {
+        predicates.join(" ")
+    }
+}
+
+/// Formats the full `st_mode`, including the file type bits, in octal and hex.
+#[must_use]
+pub fn mode_bits(mode: u32) -> String {
+    format!("(st_mode: 0o{mode:06o} 0x{mode:04x})")
+}
+
+pub const CSV_HEADER: [&str; 6] = ["type", "perms", "octal", "uid", "gid", "path"];
+
+/// Formats `fields` as a single CSV record without the line terminator.
+///
+/// # Errors
+///
+/// Returns an error if the record can't be encoded.
+pub fn csv_record<I, T>(fields: I) -> anyhow::Result<String>
+where
+    I: IntoIterator<Item = T>,
+    T: AsRef<[u8]>,
+{
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(fields)?;
+
+    let mut record = String::from_utf8(writer.into_inner()?)?;
+    record.truncate(record.trim_end_matches(['\r', '\n']).len());
+
+    Ok(record)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        ffi::OsStr,
+        fs::{self, Permissions},
+        os::{
+            linux::fs::MetadataExt,
+            unix::{ffi::OsStrExt, fs::PermissionsExt, net::UnixListener},
+        },
+        path::Path,
+        str::FromStr,
+    };
+
+    use crate::{
+        input_parser::FilterSet,
+        output::{
+            CSV_HEADER, ColorChoice, FlatPermissions, ModeDisplay, ObjectKind, QuotePaths,
+            Template, ViolationRecord, csv_record, find_command, mode_bits, rust_snippet,
+        },
+    };
+
+    #[test]
+    fn test_csv_record() {
+        assert_eq!(
+            csv_record(CSV_HEADER).unwrap(),
+            "type,perms,octal,uid,gid,path"
+        );
+        assert_eq!(
+            csv_record([
+                "file",
+                "rw-r--r--",
+                "0644",
+                "1000",
+                "1000",
+                "/srv/a,b \"quoted\".txt"
+            ])
+            .unwrap(),
+            "file,rw-r--r--,0644,1000,1000,\"/srv/a,b \"\"quoted\"\".txt\""
+        );
+    }
+
+    #[test]
+    fn test_render_colored() {
+        let red = |text: &str| format!("\x1b[1m\x1b[31m{text}\x1b[0m");
+        let green = |text: &str| format!("\x1b[1m\x1b[32m{text}\x1b[0m");
+
+        assert_eq!(ModeDisplay::Symbolic.render_colored(0o644), "rw-r--r--");
+        assert_eq!(
+            ModeDisplay::Symbolic.render_colored(0o757),
+            format!("rw{x}r-{x}r{w}{x}", x = green("x"), w = red("w"))
+        );
+        assert_eq!(
+            ModeDisplay::SymbolicSpecial.render_colored(0o4744),
+            format!("rw{}r--r--", green("s"))
+        );
+        assert_eq!(ModeDisplay::Octal.render_colored(0o666), red("0666"));
+        assert_eq!(ModeDisplay::Octal.render_colored(0o755), green("0755"));
+        assert_eq!(ModeDisplay::Octal.render_colored(0o640), "0640");
+
+        assert!(ColorChoice::Always.enabled());
+        assert!(!ColorChoice::Never.enabled());
+    }
+
+    #[test]
+    fn test_object_kind() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("socket");
+        let _listener = UnixListener::bind(&socket).unwrap();
+
+        let kind = |path: &Path| ObjectKind::from_metadata(&fs::metadata(path).unwrap(), false);
+        assert_eq!(kind(dir.path()).prefix(), 'd');
+        assert_eq!(kind(&socket), ObjectKind::Socket);
+        assert_eq!(kind(&socket).prefix(), 's');
+        assert_eq!(kind(Path::new("/dev/null")), ObjectKind::CharDevice);
+        assert_eq!(kind(Path::new("/dev/null")).prefix(), 'c');
+        assert_eq!(ObjectKind::Fifo.prefix(), 'p');
+        assert_eq!(ObjectKind::BlockDevice.name(), "block-device");
+        assert_eq!(
+            serde_json::to_string(&ObjectKind::CharDevice).unwrap(),
+            "\"char-device\""
+        );
+    }
+
+    #[test]
+    fn test_violation_json() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        let path = dir.path().join("sub").join("file");
+        fs::write(&path, "").unwrap();
+        fs::set_permissions(&path, Permissions::from_mode(0o4754)).unwrap();
+        fs::set_permissions(dir.path(), Permissions::from_mode(0o750)).unwrap();
+
+        let metadata = fs::metadata(&path).unwrap();
+        assert_eq!(
+            serde_json::to_string(&ViolationRecord::new(&path, dir.path(), &metadata, false))
+                .unwrap(),
+            format!(
+                "{{\"path\":\"{}\",\"relpath\":\"sub/file\",\"type\":\"file\",\"uid\":{},\
+                 \"gid\":{},\"mode_octal\":\"4754\",\"mode_symbolic\":\"rwsr-xr--\"}}",
+                path.to_string_lossy(),
+                metadata.st_uid(),
+                metadata.st_gid()
+            )
+        );
+
+        let metadata = fs::metadata(dir.path()).unwrap();
+        let json = serde_json::to_string(&ViolationRecord::new(
+            dir.path(),
+            dir.path(),
+            &metadata,
+            false,
+        ))
+        .unwrap();
+        assert!(json.contains("\"relpath\":\".\""), "{json}");
+        assert!(json.contains("\"type\":\"dir\""), "{json}");
+        assert!(json.contains("\"mode_symbolic\":\"rwxr-x---\""), "{json}");
+
+        // Findings outside the base directory, as with --report-under-target, have none
+        let json = serde_json::to_string(&ViolationRecord::new(
+            &path,
+            &dir.path().join("other"),
+            &metadata,
+            false,
+        ))
+        .unwrap();
+        assert!(!json.contains("relpath"), "{json}");
+    }
+
+    #[test]
+    fn test_flat_permissions_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file");
+        fs::write(&path, "").unwrap();
+        fs::set_permissions(&path, Permissions::from_mode(0o640)).unwrap();
+        let metadata = fs::metadata(&path).unwrap();
+
+        let record = ViolationRecord {
+            flat_permissions: Some(FlatPermissions::from_mode(metadata.st_mode())),
+            ..ViolationRecord::new(&path, dir.path(), &metadata, false)
+        };
+        let json: serde_json::Value = serde_json::to_value(&record).unwrap();
+        for (field, expected) in [
+            ("user_read", true),
+            ("user_write", true),
+            ("user_execute", false),
+            ("group_read", true),
+            ("group_write", false),
+            ("group_execute", false),
+            ("other_read", false),
+            ("other_write", false),
+            ("other_execute", false),
+            ("setuid", false),
+            ("setgid", false),
+            ("sticky", false),
+        ] {
+            assert_eq!(json[field], expected, "{field}");
+        }
+        assert_eq!(json["mode_symbolic"], "rw-r-----");
+
+        let json =
+            serde_json::to_string(&ViolationRecord::new(&path, dir.path(), &metadata, false))
+                .unwrap();
+        assert!(!json.contains("user_read"), "{json}");
+    }
+
+    #[test]
+    fn test_template() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("template");
+        fs::write(&path, "%t %M (%m) 100%%\n  owner: %U:%G\n  %p\n").unwrap();
+        let template = Template::parse(&fs::read_to_string(&path).unwrap()).unwrap();
+
+        let violation = ViolationRecord {
+            path: "/srv/www/index.html".to_string(),
+            relpath: Some("index.html".to_string()),
+            kind: ObjectKind::File,
+            uid: 1000,
+            gid: 33,
+            mode_octal: "0666".to_string(),
+            mode_symbolic: "rw-rw-rw-".to_string(),
+            via_symlink: None,
+            flat_permissions: None,
+        };
+        assert_eq!(
+            template.render(&violation),
+            "file rw-rw-rw- (0666) 100%\n  owner: 1000:33\n  /srv/www/index.html"
+        );
+
+        assert!(Template::parse("%p %x").is_err());
+        assert!(Template::parse("%p %").is_err());
+    }
+
+    #[test]
+    fn test_mode_display() {
+        assert_eq!(ModeDisplay::Symbolic.render(0o104_755), "rwxr-xr-x");
+        assert_eq!(ModeDisplay::SymbolicSpecial.render(0o104_755), "rwsr-xr-x");
+        assert_eq!(ModeDisplay::Octal.render(0o104_755), "4755");
+
+        assert_eq!(ModeDisplay::Symbolic.render(0o106_644), "rw-r--r--");
+        assert_eq!(ModeDisplay::SymbolicSpecial.render(0o106_644), "rwSr-Sr--");
+        assert_eq!(ModeDisplay::SymbolicSpecial.render(0o041_777), "rwxrwxrwt");
+        assert_eq!(ModeDisplay::SymbolicSpecial.render(0o041_776), "rwxrwxrwT");
+        assert_eq!(ModeDisplay::Octal.render(0o040_750), "0750");
+    }
+
+    #[test]
+    fn test_quote_paths() {
+        let plain = Path::new("/srv/www/index.html");
+        let space = Path::new("/srv/my files/it's.txt");
+        let tab = Path::new("/srv/a\tb");
+        let invalid = Path::new(OsStr::from_bytes(b"/srv/\xff"));
+
+        for quote in [QuotePaths::None, QuotePaths::Shell, QuotePaths::C] {
+            assert_eq!(quote.quote(plain), "/srv/www/index.html");
+        }
+
+        assert_eq!(QuotePaths::None.quote(space), "/srv/my files/it's.txt");
+        assert_eq!(
+            QuotePaths::Shell.quote(space),
+            r"'/srv/my files/it'\''s.txt'"
+        );
+        assert_eq!(QuotePaths::C.quote(space), r"'/srv/my files/it'\''s.txt'");
+
+        assert_eq!(QuotePaths::None.quote(tab), "/srv/a\tb");
+        assert_eq!(QuotePaths::Shell.quote(tab), "'/srv/a\tb'");
+        assert_eq!(QuotePaths::C.quote(tab), r"$'/srv/a\tb'");
+        assert_eq!(QuotePaths::C.quote(Path::new("it's\x1b")), r"$'it\'s\x1b'");
+        assert_eq!(QuotePaths::C.quote(invalid), r"$'/srv/\xff'");
+    }
+
+    #[test]
+    fn test_mode_bits() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file");
+        fs::write(&path, "").unwrap();
+        fs::set_permissions(&path, Permissions::from_mode(0o4755)).unwrap();
+        fs::set_permissions(dir.path(), Permissions::from_mode(0o1777)).unwrap();
+
+        assert_eq!(
+            mode_bits(fs::metadata(&path).unwrap().st_mode()),
+            "(st_mode: 0o104755 0x89ed)"
+        );
+        assert_eq!(
+            mode_bits(fs::metadata(dir.path()).unwrap().st_mode()),
+            "(st_mode: 0o041777 0x43ff)"
+        );
+    }
+
+    #[test]
+    fn test_find_command() {
+        assert_eq!(
+            find_command(
+                Path::new("/srv/www"),
+                &[],
+                None,
+                Some(&FilterSet::from_str("rw-r--r--u1000").unwrap())
+            ),
+            r"find /srv/www -type f ! \( -uid 1000 -perm -0644 ! -perm /0133 \)"
+        );
+        assert_eq!(
+            find_command(
+                Path::new("/my files"),
+                &["-maxdepth 2".to_string()],
+                Some(&FilterSet::from_str("g33,rwx*-----").unwrap()),
+                Some(&FilterSet::from_str("4755").unwrap())
+            ),
+            r"find '/my files' -maxdepth 2 \( -type d ! \( -gid 33 -o -perm -0700 ! -perm /0037 \) \) -o \( -type f ! \( -perm -4755 ! -perm /3022 \) \)"
+        );
+        assert_eq!(
+            find_command(
+                Path::new("/home"),
+                &[],
+                Some(&FilterSet::from_str("u1000-60000,u0-99").unwrap()),
+                None
+            ),
+            r"find /home -type d ! \( -uid +999 -uid -60001 -o -uid -100 \)"
+        );
+        assert_eq!(
+            find_command(
+                Path::new("/srv"),
+                &[],
+                None,
+                Some(&FilterSet::from_str("u0|33g33").unwrap())
+            ),
+            r"find /srv -type f ! \( \( -uid 0 -o -uid 33 \) -gid 33 \)"
+        );
+    }
+
+    #[test]
+    fn test_rust_snippet() {
+        assert_eq!(
+            rust_snippet(&FilterSet::from_str("rw-r--r*-u1000,g33").unwrap()),
+            "FilterSet {
+    filters: vec![
+        Filter {
+            user_owner: Some(IdMatch::Exact(1000)),
+            group_owner: None,
+            permissions: Some(PermissionBlock {
+                user: PartialPermissionBlock {
+                    read: PermissionState::SET,
+                    write: PermissionState::SET,
+                    execute: PermissionState::UNSET,
+                },
+                group: PartialPermissionBlock {
+                    read: PermissionState::SET,
+                    write: PermissionState::UNSET,
+                    execute: PermissionState::UNSET,
+                },
+                other: PartialPermissionBlock {
+                    read: PermissionState::SET,
+                    write: PermissionState::WILDCARD,
+                    execute: PermissionState::UNSET,
+                },
+                special: PartialPermissionBlock {
+                    read: PermissionState::WILDCARD,
+                    write: PermissionState::WILDCARD,
+                    execute: PermissionState::WILDCARD,
+                },
+            }),
+            primary_group: None,
+        },
+        Filter {
+            user_owner: None,
+            group_owner: Some(IdMatch::Exact(33)),
+            permissions: None,
+            primary_group: None,
+        },
+    ],
+}"
+        );
+        assert!(
+            rust_snippet(&FilterSet::from_str("u0|33").unwrap())
+                .contains("user_owner: Some(IdMatch::AnyOf(vec![0, 33])),")
+        );
+    }
+}
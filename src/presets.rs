@@ -0,0 +1,252 @@
+use std::{collections::HashMap, env, fs, path::Path, path::PathBuf, str::FromStr};
+
+use anyhow::{anyhow, bail};
+
+use crate::input_parser::FilterSet;
+
+/// Named pair of filters loaded from a presets file.
+///
+/// A presets file contains one `<name>.<kind> = <filter>` entry per line, where `<kind>` is
+/// either `directory` or `file` and `<filter>` uses the same syntax as `--directory-filter`
+/// and `--file-filter`. Empty lines are ignored, as is everything following the comment
+/// prefix (`#` by default), so comments can take up whole lines or trail an entry.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Preset {
+    pub directory_filter: Option<FilterSet>,
+    pub file_filter: Option<FilterSet>,
+}
+
+/// Names of the profiles shipped with permsearch.
+pub const BUILTIN_PROFILE_NAMES: [&str; 3] = ["ssh-keys", "web-root", "home-dir"];
+
+/// Profiles shipped with permsearch, in the presets file format.
+const BUILTIN_PROFILES: &str = "\
+ssh-keys.directory = rwx------
+ssh-keys.file = rw-------
+web-root.directory = 755
+web-root.file = 644
+home-dir.directory = rwx*-*---
+home-dir.file = rw**-*---
+";
+
+/// Loads the built-in profile called `name`, see [`BUILTIN_PROFILE_NAMES`].
+///
+/// # Errors
+///
+/// Returns an error if there is no profile called `name`.
+pub fn builtin_profile(name: &str) -> anyhow::Result<Preset> {
+    parse_presets(BUILTIN_PROFILES, "#")?
+        .remove(name)
+        .ok_or(anyhow!("Unknown profile \"{name}\""))
+}
+
+/// Loads the unnamed filters from the policy file at `path`.
+///
+/// A policy file uses the presets file format, but its entries have no name, like
+/// `file = rw-r--r--`.
+///
+/// # Errors
+///
+/// Returns an error if the file can't be read, contains invalid or named entries or doesn't
+/// define any filter.
+#[allow(clippy::unnecessary_debug_formatting)]
+pub fn load_policy(path: &Path, comment_prefix: &str) -> anyhow::Result<Preset> {
+    let content = fs::read_to_string(path)
+        .map_err(|err| anyhow!("Failed to read policy file {path:?}: {err}"))?;
+
+    let mut presets = parse_presets(&content, comment_prefix)?;
+    let policy = presets
+        .remove("")
+        .ok_or(anyhow!("No filters in policy file {path:?}"))?;
+
+    if let Some(name) = presets.keys().next() {
+        bail!("Unexpected preset \"{name}\" in policy file {path:?}");
+    }
+
+    Ok(policy)
+}
+
+/// Location of the presets file if `--presets-file` isn't given.
+#[must_use]
+pub fn default_presets_file() -> Option<PathBuf> {
+    let config_dir = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+
+    Some(config_dir.join("permsearch").join("presets"))
+}
+
+/// Loads the preset called `name` from the presets file at `path`.
+///
+/// # Errors
+///
+/// Returns an error if the file can't be read, contains invalid entries or doesn't define
+/// `name`.
+#[allow(clippy::unnecessary_debug_formatting)]
+pub fn load_preset(path: &Path, name: &str, comment_prefix: &str) -> anyhow::Result<Preset> {
+    let content = fs::read_to_string(path)
+        .map_err(|err| anyhow!("Failed to read presets file {path:?}: {err}"))?;
+
+    parse_presets(&content, comment_prefix)?
+        .remove(name)
+        .ok_or(anyhow!("Unknown filter preset \"{name}\" in {path:?}"))
+}
+
+fn parse_presets(content: &str, comment_prefix: &str) -> anyhow::Result<HashMap<String, Preset>> {
+    let mut presets: HashMap<String, Preset> = HashMap::new();
+
+    for (index, line) in content.lines().enumerate() {
+        let line = match line.split_once(comment_prefix) {
+            Some((content, _)) => content,
+            None => line,
+        }
+        .trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        let (key, filter) = line.split_once('=').ok_or(anyhow!(
+            "Line {}: expected \"<name>.<kind> = <filter>\"",
+            index + 1
+        ))?;
+        // Entries without a name belong to the unnamed preset of a policy file
+        let (name, kind) = key.trim().rsplit_once('.').unwrap_or(("", key.trim()));
+        let filter = FilterSet::from_str(filter.trim())
+            .map_err(|err| anyhow!("Line {}: {err}", index + 1))?;
+
+        let preset = presets.entry(name.to_string()).or_default();
+
+        match kind {
+            "directory" => preset.directory_filter = Some(filter),
+            "file" => preset.file_filter = Some(filter),
+            _ => bail!(
+                "Line {}: unknown kind \"{kind}\" (expected \"directory\" or \"file\")",
+                index + 1
+            ),
+        }
+    }
+
+    Ok(presets)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, str::FromStr};
+
+    use crate::{
+        input_parser::FilterSet,
+        presets::{
+            BUILTIN_PROFILE_NAMES, Preset, builtin_profile, load_policy, load_preset, parse_presets,
+        },
+    };
+
+    #[test]
+    fn test_load_preset() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("presets");
+        fs::write(
+            &path,
+            "# Web server content\n\
+             web-root.directory = rwxr-xr-xu33\n\
+             web-root.file = rw-r--r--u33,rw-r-----g33\n\
+             \n\
+             ssh.file = rw-------\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            load_preset(&path, "web-root", "#").unwrap(),
+            Preset {
+                directory_filter: Some(FilterSet::from_str("rwxr-xr-xu33").unwrap()),
+                file_filter: Some(FilterSet::from_str("rw-r--r--u33,rw-r-----g33").unwrap()),
+            }
+        );
+        assert_eq!(
+            load_preset(&path, "ssh", "#").unwrap(),
+            Preset {
+                directory_filter: None,
+                file_filter: Some(FilterSet::from_str("rw-------").unwrap()),
+            }
+        );
+        assert!(load_preset(&path, "missing", "#").is_err());
+        assert!(load_preset(&dir.path().join("missing"), "ssh", "#").is_err());
+    }
+
+    #[test]
+    fn test_load_policy() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("policy");
+
+        fs::write(&path, "# Stricter\nfile = rw-------\n").unwrap();
+        assert_eq!(
+            load_policy(&path, "#").unwrap(),
+            Preset {
+                directory_filter: None,
+                file_filter: Some(FilterSet::from_str("rw-------").unwrap()),
+            }
+        );
+
+        fs::write(&path, "file = rw-------\nssh.file = rw-------\n").unwrap();
+        assert!(load_policy(&path, "#").is_err());
+        fs::write(&path, "# empty\n").unwrap();
+        assert!(load_policy(&path, "#").is_err());
+    }
+
+    #[test]
+    fn test_parse_presets_errors() {
+        assert!(parse_presets("web-root rwxr-xr-x", "#").is_err());
+        assert!(parse_presets("web-root = rwxr-xr-x", "#").is_err());
+        assert!(parse_presets("web-root.socket = rwxr-xr-x", "#").is_err());
+        assert!(parse_presets("web-root.file = nonsense", "#").is_err());
+    }
+
+    #[test]
+    fn test_parse_presets_comments() {
+        let expected = Preset {
+            directory_filter: None,
+            file_filter: Some(FilterSet::from_str("rw-------u0").unwrap()),
+        };
+
+        let presets = parse_presets(
+            "# SSH keys\n\
+             ssh.file = rw-------u0 # only root\n\
+             \t# indented comment\n",
+            "#",
+        )
+        .unwrap();
+        assert_eq!(presets.len(), 1);
+        assert_eq!(presets["ssh"], expected);
+
+        let presets = parse_presets(
+            "// SSH keys\n\
+             ssh.file = rw-------u0 // only root\n\
+             # not a comment = here\n",
+            "//",
+        );
+        assert!(presets.is_err());
+
+        let presets =
+            parse_presets("// SSH keys\nssh.file = rw-------u0 // only root", "//").unwrap();
+        assert_eq!(presets["ssh"], expected);
+    }
+
+    #[test]
+    fn test_builtin_profile() {
+        for name in BUILTIN_PROFILE_NAMES {
+            let profile = builtin_profile(name).unwrap();
+            assert!(profile.directory_filter.is_some(), "{name}");
+            assert!(profile.file_filter.is_some(), "{name}");
+        }
+
+        assert_eq!(
+            builtin_profile("web-root").unwrap(),
+            Preset {
+                directory_filter: Some(FilterSet::from_str("rwxr-xr-x").unwrap()),
+                file_filter: Some(FilterSet::from_str("rw-r--r--").unwrap()),
+            }
+        );
+        assert!(builtin_profile("missing").is_err());
+    }
+}
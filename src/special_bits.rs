@@ -0,0 +1,77 @@
+/// Set-user-ID bit of `st_mode`
+pub const SETUID: u32 = 0o4000;
+/// Set-group-ID bit of `st_mode`
+pub const SETGID: u32 = 0o2000;
+/// Sticky bit of `st_mode`
+pub const STICKY: u32 = 0o1000;
+/// Write permission bits of `st_mode`
+pub const USER_WRITE: u32 = 0o200;
+pub const GROUP_WRITE: u32 = 0o020;
+pub const OTHER_WRITE: u32 = 0o002;
+
+/// Returns whether an object with `mode` owned by `uid` runs as root when executed.
+#[must_use]
+pub fn is_setuid_root(mode: u32, uid: u32) -> bool {
+    uid == 0 && mode & SETUID != 0
+}
+
+/// Returns whether an object with `mode` owned by `gid` runs with the root group when executed.
+#[must_use]
+pub fn is_setgid_root(mode: u32, gid: u32) -> bool {
+    gid == 0 && mode & SETGID != 0
+}
+
+/// Returns whether `mode` is world-writable but protected by the sticky bit, like `/tmp`.
+#[must_use]
+pub fn is_sticky_world_writable(mode: u32) -> bool {
+    mode & OTHER_WRITE != 0 && mode & STICKY != 0
+}
+
+/// Returns whether an object with `gid` didn't inherit the group of its setgid parent
+/// directory with `parent_mode` and `parent_gid`.
+#[must_use]
+pub fn breaks_setgid_inheritance(parent_mode: u32, parent_gid: u32, gid: u32) -> bool {
+    parent_mode & SETGID != 0 && gid != parent_gid
+}
+
+#[cfg(test)]
+mod test {
+    use crate::special_bits::{
+        breaks_setgid_inheritance, is_setgid_root, is_setuid_root, is_sticky_world_writable,
+    };
+
+    #[test]
+    fn test_is_setuid_root() {
+        assert!(is_setuid_root(0o104_755, 0));
+        assert!(is_setuid_root(0o106_755, 0));
+        assert!(!is_setuid_root(0o104_755, 1000));
+        assert!(!is_setuid_root(0o102_755, 0));
+        assert!(!is_setuid_root(0o101_755, 0));
+        assert!(!is_setuid_root(0o100_755, 0));
+    }
+
+    #[test]
+    fn test_is_setgid_root() {
+        assert!(is_setgid_root(0o102_755, 0));
+        assert!(is_setgid_root(0o106_755, 0));
+        assert!(!is_setgid_root(0o102_755, 1000));
+        assert!(!is_setgid_root(0o104_755, 0));
+        assert!(!is_setgid_root(0o100_755, 0));
+    }
+
+    #[test]
+    fn test_is_sticky_world_writable() {
+        assert!(is_sticky_world_writable(0o041_777));
+        assert!(is_sticky_world_writable(0o1777));
+        assert!(!is_sticky_world_writable(0o040_777));
+        assert!(!is_sticky_world_writable(0o041_775));
+        assert!(!is_sticky_world_writable(0o1755));
+    }
+
+    #[test]
+    fn test_breaks_setgid_inheritance() {
+        assert!(breaks_setgid_inheritance(0o042_775, 33, 1000));
+        assert!(!breaks_setgid_inheritance(0o042_775, 33, 33));
+        assert!(!breaks_setgid_inheritance(0o040_775, 33, 1000));
+    }
+}
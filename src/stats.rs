@@ -0,0 +1,300 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fs::Metadata,
+    os::unix::fs::{FileTypeExt, MetadataExt},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::Violation;
+
+const DAY: i64 = 24 * 60 * 60;
+
+/// Upper bounds (exclusive, in seconds) and labels of the age buckets, the last bucket
+/// holds everything older.
+pub const AGE_BUCKETS: [(i64, &str); 5] = [
+    (DAY, "<1d"),
+    (7 * DAY, "<7d"),
+    (30 * DAY, "<30d"),
+    (365 * DAY, "<1y"),
+    (i64::MAX, "older"),
+];
+
+/// Counters collected while walking the tree.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ScanStats {
+    pub scanned_files: u64,
+    pub scanned_directories: u64,
+    pub scanned_symlinks: u64,
+    pub scanned_other: u64,
+    /// Breakdown of `scanned_other`.
+    pub scanned_block_devices: u64,
+    pub scanned_char_devices: u64,
+    pub scanned_fifos: u64,
+    pub scanned_sockets: u64,
+    pub flagged: u64,
+    pub access_errors: u64,
+    pub flagged_per_directory: HashMap<PathBuf, u64>,
+    pub flagged_per_age: [u64; AGE_BUCKETS.len()],
+    /// Flagged regular files per lowercased extension, `None` for files without one.
+    pub flagged_per_extension: HashMap<Option<String>, u64>,
+    pub owners_per_mode: HashMap<PathBuf, HashMap<u32, BTreeSet<u32>>>,
+    pub profile: Profile,
+    /// Mode, uid and gid of the directories shown by `--show-parent-chain`.
+    pub ancestor_cache: HashMap<PathBuf, (u32, u32, u32)>,
+    /// Set once an object without a creation time was encountered.
+    pub creation_time_unsupported: bool,
+    pub policy_comparison: PolicyComparison,
+    /// Device and inode numbers of the directories searched with `--follow-symlinks`.
+    pub visited_directories: HashSet<(u64, u64)>,
+    /// Symlink through which the directory being searched was reached with
+    /// `--report-under-target`.
+    pub via_symlink: Option<PathBuf>,
+    /// Examined objects per permission bits, including the special bits.
+    pub mode_histogram: BTreeMap<u32, u64>,
+    /// Set once writing to the `--trace-json` file failed.
+    pub trace_failed: bool,
+    /// Access errors held back by `--defer-errors` until the end of the search.
+    pub deferred_errors: Vec<String>,
+    /// Flagged objects, collected instead of printed by [`crate::scan`].
+    pub violations: Vec<Violation>,
+}
+
+/// Objects violating the active filters, the `--compare-policy` filters or both.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PolicyComparison {
+    pub both: u64,
+    pub only_current: u64,
+    pub only_other: u64,
+}
+
+impl PolicyComparison {
+    /// Counts an object by the policies it violates.
+    pub fn count(&mut self, violates_current: bool, violates_other: bool) {
+        match (violates_current, violates_other) {
+            (true, true) => self.both += 1,
+            (true, false) => self.only_current += 1,
+            (false, true) => self.only_other += 1,
+            (false, false) => {}
+        }
+    }
+
+    #[must_use]
+    pub fn summary(&self, other: &Path) -> String {
+        format!(
+            "Policy comparison:\n{: >7} violate both\n{: >7} violate only the current filters\n\
+             {: >7} violate only {}",
+            self.both,
+            self.only_current,
+            self.only_other,
+            other.to_string_lossy()
+        )
+    }
+}
+
+/// Time spent in the phases of a search.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Profile {
+    pub read_dir: Duration,
+    pub metadata: Duration,
+    pub output: Duration,
+}
+
+impl Profile {
+    /// Formats the phases and the remaining time of a search that took `total`.
+    #[must_use]
+    pub fn summary(&self, total: Duration) -> String {
+        let measured = self.read_dir + self.metadata + self.output;
+
+        [
+            ("directory reads", self.read_dir),
+            ("metadata", self.metadata),
+            ("output", self.output),
+            ("other", total.saturating_sub(measured)),
+            ("total", total),
+        ]
+        .iter()
+        .fold("Profile:".to_string(), |summary, (phase, duration)| {
+            format!(
+                "{summary}\n  {phase: <16} {: >10.6}s",
+                duration.as_secs_f64()
+            )
+        })
+    }
+}
+
+impl ScanStats {
+    /// Counts an examined object, no matter if it's flagged or not.
+    pub fn count_scanned(&mut self, metadata: &Metadata, is_symlink: bool) {
+        if is_symlink {
+            self.scanned_symlinks += 1;
+        } else if metadata.is_dir() {
+            self.scanned_directories += 1;
+        } else if metadata.is_file() {
+            self.scanned_files += 1;
+        } else {
+            self.scanned_other += 1;
+
+            let file_type = metadata.file_type();
+            if file_type.is_block_device() {
+                self.scanned_block_devices += 1;
+            } else if file_type.is_char_device() {
+                self.scanned_char_devices += 1;
+            } else if file_type.is_fifo() {
+                self.scanned_fifos += 1;
+            } else if file_type.is_socket() {
+                self.scanned_sockets += 1;
+            }
+        }
+    }
+
+    /// Counts a flagged object in its parent directory.
+    pub fn count_flagged_in(&mut self, path: &Path) {
+        if let Some(parent) = path.parent() {
+            *self
+                .flagged_per_directory
+                .entry(parent.to_path_buf())
+                .or_default() += 1;
+        }
+    }
+
+    /// Counts a flagged object in the age bucket of its modification time relative to `now`.
+    pub fn count_flagged_age(&mut self, metadata: &Metadata, now: i64) {
+        let age = now.saturating_sub(metadata.mtime());
+        let bucket = AGE_BUCKETS
+            .iter()
+            .position(|(limit, _)| age < *limit)
+            .unwrap_or(AGE_BUCKETS.len() - 1);
+
+        self.flagged_per_age[bucket] += 1;
+    }
+
+    /// Counts a flagged regular file under its lowercased extension.
+    pub fn count_flagged_extension(&mut self, path: &Path) {
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+
+        *self.flagged_per_extension.entry(extension).or_default() += 1;
+    }
+
+    /// Returns the extensions of flagged files, the most common first.
+    #[must_use]
+    pub fn extension_distribution(&self) -> Vec<(Option<&str>, u64)> {
+        let mut extensions: Vec<(Option<&str>, u64)> = self
+            .flagged_per_extension
+            .iter()
+            .map(|(extension, flagged)| (extension.as_deref(), *flagged))
+            .collect();
+
+        extensions.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+        extensions
+    }
+
+    /// Records the owner of an object under its permission bits in its parent directory.
+    pub fn count_owner(&mut self, path: &Path, metadata: &Metadata) {
+        if let Some(parent) = path.parent() {
+            self.owners_per_mode
+                .entry(parent.to_path_buf())
+                .or_default()
+                .entry(metadata.mode() & 0o7777)
+                .or_default()
+                .insert(metadata.uid());
+        }
+    }
+
+    /// Returns the modes shared by objects of more than one owner in the same directory,
+    /// sorted by directory and mode.
+    #[must_use]
+    pub fn owner_inconsistencies(&self) -> Vec<(&Path, u32, Vec<u32>)> {
+        let mut inconsistencies: Vec<(&Path, u32, Vec<u32>)> = self
+            .owners_per_mode
+            .iter()
+            .flat_map(|(directory, modes)| {
+                modes
+                    .iter()
+                    .filter(|(_, owners)| owners.len() > 1)
+                    .map(|(mode, owners)| {
+                        (directory.as_path(), *mode, owners.iter().copied().collect())
+                    })
+            })
+            .collect();
+
+        inconsistencies.sort_by(|a, b| a.0.cmp(b.0).then(a.1.cmp(&b.1)));
+
+        inconsistencies
+    }
+
+    /// Returns the `count` directories containing the most flagged objects.
+    #[must_use]
+    pub fn worst_directories(&self, count: usize) -> Vec<(&Path, u64)> {
+        let mut directories: Vec<(&Path, u64)> = self
+            .flagged_per_directory
+            .iter()
+            .map(|(path, flagged)| (path.as_path(), *flagged))
+            .collect();
+
+        directories.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        directories.truncate(count);
+
+        directories
+    }
+
+    /// Counts an examined object under its permission bits.
+    pub fn count_mode(&mut self, metadata: &Metadata) {
+        *self
+            .mode_histogram
+            .entry(metadata.mode() & 0o7777)
+            .or_default() += 1;
+    }
+
+    /// Formats the number of examined objects per mode, sorted by mode.
+    #[must_use]
+    pub fn mode_histogram_summary(&self) -> String {
+        self.mode_histogram.iter().fold(
+            "Permission histogram:".to_string(),
+            |summary, (mode, count)| format!("{summary}\n   {mode:04o} {count: >7}"),
+        )
+    }
+
+    /// Formats the number of examined objects of every file type, one per line.
+    #[must_use]
+    pub fn types_summary(&self) -> String {
+        [
+            (self.scanned_files, "files"),
+            (self.scanned_directories, "directories"),
+            (self.scanned_symlinks, "symlinks"),
+            (self.scanned_block_devices, "block devices"),
+            (self.scanned_char_devices, "character devices"),
+            (self.scanned_fifos, "fifos"),
+            (self.scanned_sockets, "sockets"),
+        ]
+        .iter()
+        .fold("Object types:".to_string(), |summary, (count, name)| {
+            format!("{summary}\n{count: >7} {name}")
+        })
+    }
+
+    /// Formats the one-line summary printed after every non-silent search.
+    #[must_use]
+    pub fn summary(&self) -> String {
+        let examined = self.scanned_files
+            + self.scanned_directories
+            + self.scanned_symlinks
+            + self.scanned_other;
+
+        format!(
+            "Examined {examined} objects ({} files, {} directories, {} symlinks), {} violations",
+            self.scanned_files, self.scanned_directories, self.scanned_symlinks, self.flagged
+        )
+    }
+
+    #[must_use]
+    pub fn scanned_summary(&self) -> String {
+        format!(
+            "Scanned: {} files, {} directories, {} symlinks, {} other",
+            self.scanned_files, self.scanned_directories, self.scanned_symlinks, self.scanned_other
+        )
+    }
+}
@@ -1,24 +1,617 @@
-use std::process::exit;
+use std::{
+    collections::HashSet,
+    env,
+    ffi::OsStr,
+    fs::{self, FileType},
+    io,
+    os::unix::fs::{FileTypeExt, MetadataExt},
+    path::{Component, Path, PathBuf},
+    process::{Command, exit},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use clap::builder::styling::{AnsiColor, Color, Style};
 
+/// Prints `msg` and exits with status 2, as 1 signals findings.
 pub fn exit_with_error(msg: &str) -> ! {
     print_error(msg);
-    exit(1);
+    exit(2);
 }
 
 pub fn print_error(msg: &str) {
-    let style = Style::new()
-        .bold()
-        .fg_color(Some(Color::Ansi(AnsiColor::Red)));
-
-    eprintln!("{style}error{style:#}: {msg}");
+    eprintln!("{}: {msg}", label("error", AnsiColor::Red, no_color()));
 }
 
 pub fn print_access_error(msg: &str) {
-    let style = Style::new()
-        .bold()
-        .fg_color(Some(Color::Ansi(AnsiColor::Red)));
+    eprintln!("{} {msg}", label("Error", AnsiColor::Red, no_color()));
+}
+
+pub fn print_warning(msg: &str) {
+    eprintln!("{}: {msg}", label("warning", AnsiColor::Yellow, no_color()));
+}
+
+/// Returns whether styling is disabled by a non-empty `NO_COLOR` environment variable, see
+/// <https://no-color.org>.
+#[must_use]
+pub fn no_color() -> bool {
+    env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty())
+}
+
+/// Formats `text` bold in `color`, or plain if `plain` is set.
+fn label(text: &str, color: AnsiColor, plain: bool) -> String {
+    if plain {
+        return text.to_string();
+    }
+
+    let style = Style::new().bold().fg_color(Some(Color::Ansi(color)));
+
+    format!("{style}{text}{style:#}")
+}
+
+/// Parses a duration like `90s`, `15m`, `12h`, `7d` or `2w`, a plain number means seconds.
+///
+/// # Errors
+///
+/// Returns an error if the number or the unit is invalid.
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let split = value
+        .find(|char: char| !char.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration \"{value}\""))?;
+    let factor = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => {
+            return Err(format!(
+                "unknown unit \"{unit}\" (expected s, m, h, d or w)"
+            ));
+        }
+    };
+
+    number
+        .checked_mul(factor)
+        .map(Duration::from_secs)
+        .ok_or(format!("duration \"{value}\" is too long"))
+}
+
+/// Parses a point in time as seconds since the Unix epoch: a duration like `30d` before `now`
+/// (see [`parse_duration`]), `@SECONDS` or a UTC date `YYYY-MM-DD` with an optional time
+/// `THH:MM[:SS]`.
+///
+/// # Errors
+///
+/// Returns an error if `value` is none of these or names an invalid date.
+pub fn parse_timestamp(value: &str, now: i64) -> Result<i64, String> {
+    if let Some(seconds) = value.strip_prefix('@') {
+        return seconds
+            .parse()
+            .map_err(|_| format!("invalid timestamp \"{value}\""));
+    }
+
+    if !value.contains('-') {
+        let duration = parse_duration(value)?;
+        return i64::try_from(duration.as_secs())
+            .ok()
+            .and_then(|seconds| now.checked_sub(seconds))
+            .ok_or(format!("duration \"{value}\" is too long"));
+    }
+
+    let invalid = || format!("invalid date \"{value}\" (expected YYYY-MM-DD[THH:MM[:SS]])");
+    let numbers = |text: &str, separator: char| -> Result<Vec<i64>, String> {
+        text.split(separator)
+            .map(|number| number.parse().map_err(|_| invalid()))
+            .collect()
+    };
+
+    let (date, time) = value.split_once('T').unwrap_or((value, "00:00"));
+    let [year, month, day] = numbers(date, '-')?[..] else {
+        return Err(invalid());
+    };
+    let (hour, minute, second) = match numbers(time, ':')?[..] {
+        [hour, minute] => (hour, minute, 0),
+        [hour, minute, second] => (hour, minute, second),
+        _ => return Err(invalid()),
+    };
+
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days_in_month = match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    };
+    if !(1..=12).contains(&month)
+        || !(1..=days_in_month).contains(&day)
+        || !(0..24).contains(&hour)
+        || !(0..60).contains(&minute)
+        || !(0..60).contains(&second)
+    {
+        return Err(invalid());
+    }
+
+    Ok(days_from_civil(year, month, day) * 24 * 60 * 60 + hour * 60 * 60 + minute * 60 + second)
+}
+
+/// Days between the Unix epoch and a date of the proleptic Gregorian calendar, see
+/// <https://howardhinnant.github.io/date_algorithms.html#days_from_civil>.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Parses a size like `500k`, `10M`, `2G` or `1T` with binary units, a plain number means
+/// bytes.
+///
+/// # Errors
+///
+/// Returns an error if the number or the unit is invalid.
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let split = value
+        .find(|char: char| !char.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid size \"{value}\""))?;
+    let factor: u64 = match unit {
+        "" => 1,
+        "k" | "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        "T" => 1 << 40,
+        _ => {
+            return Err(format!("unknown unit \"{unit}\" (expected k, M, G or T)"));
+        }
+    };
+
+    number
+        .checked_mul(factor)
+        .ok_or(format!("size \"{value}\" is too large"))
+}
+
+/// Current time in seconds since the Unix epoch, comparable to `st_mtime`.
+#[must_use]
+pub fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| {
+            i64::try_from(duration.as_secs()).unwrap_or(i64::MAX)
+        })
+}
+
+/// Collapses `.` and `..` components of `path` without touching the filesystem.
+///
+/// Leading `..` components of relative paths are kept, while `..` directly below the root
+/// is dropped.
+#[must_use]
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                Some(Component::RootDir | Component::Prefix(_)) => {}
+                Some(Component::ParentDir | Component::CurDir) | None => {
+                    normalized.push(component);
+                }
+            },
+            _ => normalized.push(component),
+        }
+    }
+
+    if normalized.as_os_str().is_empty() {
+        normalized.push(Component::CurDir);
+    }
+
+    normalized
+}
+
+/// Returns whether `path` is part of a deterministic sample of about 1 in `ratio` paths.
+#[must_use]
+pub fn is_sampled(path: &Path, ratio: u32) -> bool {
+    path_hash(path).is_multiple_of(u64::from(ratio))
+}
+
+/// 64-bit FNV-1a hash of `path`, stable across runs and platforms.
+#[must_use]
+pub fn path_hash(path: &Path) -> u64 {
+    path.as_os_str()
+        .as_encoded_bytes()
+        .iter()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01b3)
+        })
+}
+
+/// Shuffles `items` in a way that only depends on `seed` (Fisher-Yates driven by `SplitMix64`).
+pub fn shuffle<T>(items: &mut [T], seed: u64) {
+    let mut state = seed;
+    let mut next = || {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    };
+
+    for i in (1..items.len()).rev() {
+        let j = next() % (i as u64 + 1);
+        items.swap(i, usize::try_from(j).unwrap_or(i));
+    }
+}
+
+/// Describes what the symlink at `path` points to: `dir`, `file` or `broken`.
+#[must_use]
+pub fn symlink_target_type(path: &Path) -> &'static str {
+    match path.metadata() {
+        Ok(metadata) if metadata.is_dir() => "dir",
+        Ok(_) => "file",
+        Err(_) => "broken",
+    }
+}
+
+/// Number of hops after which [`symlink_chain`] gives up, like the kernel.
+pub const MAX_SYMLINK_HOPS: usize = 40;
+
+/// Follows the symlink at `path` hop by hop and returns one `-> target` line per hop.
+///
+/// The chain ends at the first object that isn't a symlink. A final `(broken)`, `(loop)` or
+/// `(too long)` line is added if it ends at a missing target, returns to a link seen before
+/// or exceeds [`MAX_SYMLINK_HOPS`].
+#[must_use]
+pub fn symlink_chain(path: &Path) -> Vec<String> {
+    let mut hops = Vec::new();
+    let mut visited = HashSet::from([path.to_path_buf()]);
+    let mut current = path.to_path_buf();
+
+    while let Ok(target) = fs::read_link(&current) {
+        hops.push(format!("-> {}", target.to_string_lossy()));
+
+        let next = match current.parent() {
+            Some(parent) => parent.join(&target),
+            None => target,
+        };
+
+        if !visited.insert(next.clone()) {
+            hops.push("(loop)".to_string());
+            break;
+        }
+
+        match next.symlink_metadata() {
+            Ok(metadata) if metadata.is_symlink() => {
+                if hops.len() >= MAX_SYMLINK_HOPS {
+                    hops.push("(too long)".to_string());
+                    break;
+                }
+
+                current = next;
+            }
+            Ok(_) => break,
+            Err(_) => {
+                hops.push("(broken)".to_string());
+                break;
+            }
+        }
+    }
+
+    hops
+}
+
+/// Returns the author of the last commit touching `path`.
+///
+/// Returns `None` if `path` isn't tracked by git or git isn't available.
+#[must_use]
+pub fn git_last_author(path: &Path) -> Option<String> {
+    let parent = path.parent()?;
+    let file_name = path.file_name()?;
+
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(if parent.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            parent
+        })
+        .args(["log", "-1", "--format=%an", "--"])
+        .arg(file_name)
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    let author = String::from_utf8_lossy(&output.stdout).trim().to_string();
+
+    if author.is_empty() {
+        None
+    } else {
+        Some(author)
+    }
+}
+
+/// Returns whether `path` is the root of a mounted filesystem.
+///
+/// # Errors
+///
+/// Returns an error if `path` or its parent can't be accessed.
+pub fn is_mount_root(path: &Path) -> io::Result<bool> {
+    let path = path.canonicalize()?;
+
+    match path.parent() {
+        Some(parent) => Ok(parent.metadata()?.dev() != path.metadata()?.dev()),
+        None => Ok(true),
+    }
+}
+
+/// Returns whether a directory called `name` directly below a mount root holds device nodes.
+#[must_use]
+pub fn is_device_directory(name: &OsStr) -> bool {
+    name == "dev"
+}
+
+/// Returns whether `file_type` is a block or character device.
+#[must_use]
+pub fn is_device_node(file_type: &FileType) -> bool {
+    file_type.is_block_device() || file_type.is_char_device()
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        ffi::OsStr, fs, os::unix::fs::symlink, path::Path, process::Command, time::Duration,
+    };
+
+    use clap::builder::styling::AnsiColor;
+
+    use crate::util::{
+        git_last_author, is_device_directory, is_device_node, is_mount_root, is_sampled, label,
+        normalize_path, parse_duration, parse_size, parse_timestamp, shuffle, symlink_chain,
+        symlink_target_type,
+    };
+
+    #[test]
+    #[ignore = "requires git"]
+    fn test_git_last_author() {
+        let repo = tempfile::tempdir().unwrap();
+        let git = |args: &[&str]| {
+            let status = Command::new("git")
+                .arg("-C")
+                .arg(repo.path())
+                .args(args)
+                .status()
+                .unwrap();
+            assert!(status.success());
+        };
+
+        git(&["init", "-q"]);
+        fs::write(repo.path().join("tracked"), "").unwrap();
+        fs::write(repo.path().join("untracked"), "").unwrap();
+        git(&["add", "tracked"]);
+        git(&[
+            "-c",
+            "user.name=Jane Doe",
+            "-c",
+            "user.email=jane@example.com",
+            "commit",
+            "-q",
+            "-m",
+            "add tracked",
+        ]);
+
+        assert_eq!(
+            git_last_author(&repo.path().join("tracked")),
+            Some("Jane Doe".to_string())
+        );
+        assert_eq!(git_last_author(&repo.path().join("untracked")), None);
+
+        let no_repo = tempfile::tempdir().unwrap();
+        fs::write(no_repo.path().join("file"), "").unwrap();
+        assert_eq!(git_last_author(&no_repo.path().join("file")), None);
+    }
+
+    #[test]
+    fn test_safe_root_seams() {
+        assert!(is_mount_root(Path::new("/")).unwrap());
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("file"), "").unwrap();
+        assert!(!is_mount_root(&dir.path().join("sub")).unwrap());
+
+        assert!(is_device_directory(OsStr::new("dev")));
+        assert!(!is_device_directory(OsStr::new("devices")));
+
+        let file_type = |path: &Path| fs::symlink_metadata(path).unwrap().file_type();
+        assert!(is_device_node(&file_type(Path::new("/dev/null"))));
+        assert!(!is_device_node(&file_type(&dir.path().join("file"))));
+        assert!(!is_device_node(&file_type(&dir.path().join("sub"))));
+    }
+
+    #[test]
+    fn test_normalize_path() {
+        let normalized = |path: &str| normalize_path(Path::new(path));
+
+        assert_eq!(normalized("/srv/app/../app"), Path::new("/srv/app"));
+        assert_eq!(normalized("/srv/./app/"), Path::new("/srv/app"));
+        assert_eq!(normalized("/srv/app/../../.."), Path::new("/"));
+        assert_eq!(normalized("src/../target"), Path::new("target"));
+        assert_eq!(normalized("../src/./.."), Path::new(".."));
+        assert_eq!(normalized("src/.."), Path::new("."));
+        assert_eq!(normalized("."), Path::new("."));
+        assert_eq!(
+            normalized("/srv/app/../app").join("index.html"),
+            Path::new("/srv/app/index.html")
+        );
+    }
+
+    #[test]
+    fn test_is_sampled() {
+        let paths: Vec<_> = (0..20_000)
+            .map(|i| {
+                Path::new("/srv")
+                    .join(format!("dir{}", i % 7))
+                    .join(format!("file{i}"))
+            })
+            .collect();
+        let sampled = |ratio| {
+            paths
+                .iter()
+                .filter(|path| is_sampled(path, ratio))
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(sampled(1).len(), paths.len());
+
+        let first = sampled(10);
+        assert!((1_800..=2_200).contains(&first.len()), "{}", first.len());
+        assert_eq!(first, sampled(10));
+    }
+
+    #[test]
+    fn test_symlink_target_type() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("file"), "").unwrap();
+        fs::create_dir(dir.path().join("dir")).unwrap();
+        symlink(dir.path().join("file"), dir.path().join("to_file")).unwrap();
+        symlink(dir.path().join("dir"), dir.path().join("to_dir")).unwrap();
+        symlink(dir.path().join("missing"), dir.path().join("to_nothing")).unwrap();
+
+        assert_eq!(symlink_target_type(&dir.path().join("to_file")), "file");
+        assert_eq!(symlink_target_type(&dir.path().join("to_dir")), "dir");
+        assert_eq!(
+            symlink_target_type(&dir.path().join("to_nothing")),
+            "broken"
+        );
+    }
+
+    #[test]
+    fn test_symlink_chain() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("file"), "").unwrap();
+        symlink("file", dir.path().join("hop2")).unwrap();
+        symlink(dir.path().join("hop2"), dir.path().join("hop1")).unwrap();
+        symlink("missing", dir.path().join("broken")).unwrap();
+        symlink("loop_b", dir.path().join("loop_a")).unwrap();
+        symlink("loop_a", dir.path().join("loop_b")).unwrap();
+
+        assert_eq!(
+            symlink_chain(&dir.path().join("hop1")),
+            vec![
+                format!("-> {}", dir.path().join("hop2").to_string_lossy()),
+                "-> file".to_string(),
+            ]
+        );
+        assert_eq!(
+            symlink_chain(&dir.path().join("broken")),
+            vec!["-> missing", "(broken)"]
+        );
+        assert_eq!(
+            symlink_chain(&dir.path().join("loop_a")),
+            vec!["-> loop_b", "-> loop_a", "(loop)"]
+        );
+        assert!(symlink_chain(&dir.path().join("file")).is_empty());
+    }
+
+    #[test]
+    fn test_shuffle() {
+        let shuffled = |seed| {
+            let mut items: Vec<u32> = (0..50).collect();
+            shuffle(&mut items, seed);
+            items
+        };
+
+        assert_eq!(shuffled(42), shuffled(42));
+        assert_ne!(shuffled(42), shuffled(43));
+        assert_ne!(shuffled(42), (0..50).collect::<Vec<_>>());
+
+        let mut sorted = shuffled(42);
+        sorted.sort_unstable();
+        assert_eq!(sorted, (0..50).collect::<Vec<_>>());
+
+        let mut empty: [u32; 0] = [];
+        shuffle(&mut empty, 42);
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("15m"), Ok(Duration::from_mins(15)));
+        assert_eq!(parse_duration("12h"), Ok(Duration::from_hours(12)));
+        assert_eq!(parse_duration("7d"), Ok(Duration::from_hours(7 * 24)));
+        assert_eq!(parse_duration("2w"), Ok(Duration::from_hours(14 * 24)));
+
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("d").is_err());
+        assert!(parse_duration("7y").is_err());
+        assert!(parse_duration("-7d").is_err());
+        assert!(parse_duration("99999999999999999999w").is_err());
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        let now = 1_700_000_000;
+        assert_eq!(parse_timestamp("30d", now), Ok(now - 30 * 24 * 60 * 60));
+        assert_eq!(parse_timestamp("6h", now), Ok(now - 6 * 60 * 60));
+        assert_eq!(parse_timestamp("90", now), Ok(now - 90));
+        assert_eq!(parse_timestamp("@1234", now), Ok(1234));
+        assert_eq!(parse_timestamp("@-1", now), Ok(-1));
+        assert_eq!(parse_timestamp("1970-01-01", now), Ok(0));
+        assert_eq!(parse_timestamp("1969-12-31T23:59:59", now), Ok(-1));
+        assert_eq!(parse_timestamp("2000-03-01", now), Ok(951_868_800));
+        assert_eq!(parse_timestamp("2024-02-29T12:30", now), Ok(1_709_209_800));
+
+        assert!(parse_timestamp("2023-02-29", now).is_err());
+        assert!(parse_timestamp("2024-13-01", now).is_err());
+        assert!(parse_timestamp("2024-01-01T24:00", now).is_err());
+        assert!(parse_timestamp("2024-01-01T12", now).is_err());
+        assert!(parse_timestamp("2024-01", now).is_err());
+        assert!(parse_timestamp("@soon", now).is_err());
+        assert!(parse_timestamp("7y", now).is_err());
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("0"), Ok(0));
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("500k"), Ok(500 * 1024));
+        assert_eq!(parse_size("500K"), Ok(500 * 1024));
+        assert_eq!(parse_size("10M"), Ok(10 * 1024 * 1024));
+        assert_eq!(parse_size("2G"), Ok(2 * 1024 * 1024 * 1024));
+        assert_eq!(parse_size("1T"), Ok(1 << 40));
+
+        assert!(parse_size("").is_err());
+        assert!(parse_size("M").is_err());
+        assert!(parse_size("10m").is_err());
+        assert!(parse_size("1.5G").is_err());
+        assert!(parse_size("99999999999T").is_err());
+    }
 
-    eprintln!("{style}Error{style:#} {msg}");
+    #[test]
+    fn test_label() {
+        assert_eq!(label("error", AnsiColor::Red, true), "error");
+        assert_eq!(
+            label("error", AnsiColor::Red, false),
+            "\x1b[1m\x1b[31merror\x1b[0m"
+        );
+    }
 }